@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use robot_serial::protocol::controller::{A, DOWN, UP};
+
+use crate::{controller::Controller, path::Path};
+
+/// Named autonomous routines. Each is built on demand so the same auton can
+/// be run again after a restart of the competition state.
+#[derive(Default)]
+pub struct AutonRegistry {
+    autons: Vec<(String, Box<dyn Fn() -> Path>)>,
+}
+
+impl AutonRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add<F: Fn() -> Path + 'static>(&mut self, name: &str, build: F) -> &mut Self {
+        self.autons.push((name.to_owned(), Box::new(build)));
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.autons.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.autons.is_empty()
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.autons.iter().map(|(name, _)| name.as_str())
+    }
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.names().position(|v| v == name)
+    }
+    pub fn name(&self, idx: usize) -> Option<&str> {
+        self.autons.get(idx).map(|(name, _)| name.as_str())
+    }
+    pub fn build(&self, idx: usize) -> Option<Path> {
+        self.autons.get(idx).map(|(_, build)| build())
+    }
+}
+
+/// Picks an auton with the controller while the robot is disabled. Up and
+/// down on the D-pad cycle through the autons and A confirms the choice,
+/// which is saved to `save_path` so it survives a restart. Only the
+/// confirmed auton is run.
+pub struct AutonSelector {
+    registry: AutonRegistry,
+    selected: usize,
+    confirmed: usize,
+    save_path: PathBuf,
+}
+
+impl AutonSelector {
+    pub fn new<P: Into<PathBuf>>(registry: AutonRegistry, save_path: P) -> Self {
+        assert!(!registry.is_empty(), "no autons to select from");
+        let save_path = save_path.into();
+        let confirmed = match std::fs::read_to_string(&save_path) {
+            Ok(name) => registry.position(name.trim()).unwrap_or_else(|| {
+                log::warn!("Saved auton {:?} no longer exists", name.trim());
+                0
+            }),
+            Err(_) => 0,
+        };
+        let selector = Self {
+            registry,
+            selected: confirmed,
+            confirmed,
+            save_path,
+        };
+        log::info!("Confirmed auton: {}", selector.confirmed_name());
+        selector
+    }
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+    pub fn selected_name(&self) -> &str {
+        self.registry.name(self.selected).unwrap()
+    }
+    pub fn confirmed(&self) -> usize {
+        self.confirmed
+    }
+    pub fn confirmed_name(&self) -> &str {
+        self.registry.name(self.confirmed).unwrap()
+    }
+    pub fn registry(&self) -> &AutonRegistry {
+        &self.registry
+    }
+    /// Should be called every loop while the robot is disabled.
+    pub fn update(&mut self, controller: &Controller) {
+        let count = self.registry.len();
+        if controller.pressed(UP) {
+            self.selected = (self.selected + count - 1) % count;
+            log::info!("Auton: {}", self.selected_name());
+        }
+        if controller.pressed(DOWN) {
+            self.selected = (self.selected + 1) % count;
+            log::info!("Auton: {}", self.selected_name());
+        }
+        if controller.pressed(A) {
+            self.confirmed = self.selected;
+            match std::fs::write(&self.save_path, self.confirmed_name()) {
+                Ok(()) => log::info!("Confirmed auton: {}", self.confirmed_name()),
+                Err(e) => log::error!("Failed to save auton choice: {e}"),
+            }
+        }
+    }
+    /// Builds a fresh copy of the confirmed auton.
+    pub fn build(&self) -> Path {
+        if self.selected != self.confirmed {
+            log::warn!(
+                "Auton {} was selected but not confirmed with A",
+                self.selected_name()
+            );
+        }
+        log::info!("Running auton: {}", self.confirmed_name());
+        self.registry.build(self.confirmed).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use robot_serial::protocol::{ControllerButtons, ControllerState, ToRobot};
+
+    use super::*;
+
+    // a controller that has just had `button` pressed
+    fn press(button: ControllerButtons) -> Controller {
+        let pressed = ToRobot {
+            controller_state: Some(ControllerState {
+                buttons: button,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        [pressed, ToRobot::default()].into()
+    }
+
+    #[test]
+    fn only_confirmed_auton_runs() {
+        let save_path =
+            std::env::temp_dir().join(format!("auton-test-{}.txt", std::process::id()));
+        let built = Rc::new(Cell::new(None));
+        let mut registry = AutonRegistry::new();
+        for (idx, name) in ["left", "right"].into_iter().enumerate() {
+            let built = built.clone();
+            registry.add(name, move || {
+                built.set(Some(idx));
+                Path::new(Vec::new())
+            });
+        }
+        std::fs::write(&save_path, "left").unwrap();
+        let mut selector = AutonSelector::new(registry, &save_path);
+
+        selector.update(&press(DOWN));
+        assert_eq!(selector.selected_name(), "right");
+        selector.build();
+        assert_eq!(built.get(), Some(0));
+        assert_eq!(std::fs::read_to_string(&save_path).unwrap(), "left");
+
+        selector.update(&press(A));
+        selector.build();
+        assert_eq!(built.get(), Some(1));
+        assert_eq!(std::fs::read_to_string(&save_path).unwrap(), "right");
+        std::fs::remove_file(&save_path).unwrap();
+    }
+}
@@ -1,25 +1,12 @@
 use std::{f64, time::Duration};
 
 use communication::RobotInfo;
-use modifier_path::*;
-use path::*;
-use ramsete::Ramsete;
+use firecracker::{
+    brain, drivebase, imu, modifier_path::*, odometry, path::*, pid, ramsete::Ramsete,
+    vec::Vec2,
+};
 use robot_serial::protocol::{controller::*, *};
 
-mod brain;
-mod controller;
-mod drivebase;
-mod imu;
-mod latch;
-mod modifier_path;
-mod odometry;
-mod path;
-mod pid;
-mod ramsete;
-mod vec;
-
-use vec::Vec2;
-
 // cartesion coordinate space
 //
 // rl and rr = 254 mm
@@ -1,13 +1,82 @@
+use std::collections::VecDeque;
+
 use robot_serial::{protocol::*, BrainMediator};
 
-use crate::controller::Controller;
+use crate::{
+    clock,
+    controller::Controller,
+    record::{RecordError, Recorder},
+};
+
+/// Source of `ToRobot` packets and sink for `ToBrain` packets.
+///
+/// This is implemented by the serial `BrainMediator` when running on the
+/// robot and by `MockBrain` (or a simulator) when running off-robot, so the
+/// same main loop can be driven by either.
+pub trait BrainIo {
+    /// Returns the newest packet from the brain or
+    /// `robot_serial::Error::NoPacketRead` if nothing new has arrived.
+    fn read_packet(&mut self) -> Result<ToRobot, robot_serial::Error>;
+    fn write_packet(&mut self, pkt: &ToBrain) -> Result<(), robot_serial::Error>;
+}
+
+impl BrainIo for BrainMediator {
+    fn read_packet(&mut self) -> Result<ToRobot, robot_serial::Error> {
+        self.try_read().map(|pkt| pkt.to_owned())
+    }
+    fn write_packet(&mut self, pkt: &ToBrain) -> Result<(), robot_serial::Error> {
+        self.try_write(pkt).map(|_| ())
+    }
+}
+
+/// In-process stand-in for the brain. Packets queued with `push` are
+/// handed out in order and every written packet is kept for inspection.
+#[derive(Debug, Default)]
+pub struct MockBrain {
+    incoming: VecDeque<ToRobot>,
+    written: Vec<ToBrain>,
+}
 
-pub struct Brain {
-    mediator: BrainMediator,
+impl MockBrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, pkt: ToRobot) {
+        self.incoming.push_back(pkt);
+    }
+    pub fn pending(&self) -> usize {
+        self.incoming.len()
+    }
+    pub fn written(&self) -> &[ToBrain] {
+        &self.written
+    }
+    pub fn last_written(&self) -> Option<&ToBrain> {
+        self.written.last()
+    }
+    pub fn take_written(&mut self) -> Vec<ToBrain> {
+        std::mem::take(&mut self.written)
+    }
+}
+
+impl BrainIo for MockBrain {
+    fn read_packet(&mut self) -> Result<ToRobot, robot_serial::Error> {
+        self.incoming
+            .pop_front()
+            .ok_or(robot_serial::Error::NoPacketRead)
+    }
+    fn write_packet(&mut self, pkt: &ToBrain) -> Result<(), robot_serial::Error> {
+        self.written.push(pkt.clone());
+        Ok(())
+    }
+}
+
+pub struct Brain<B: BrainIo = BrainMediator> {
+    io: B,
     packet_buffer: [ToRobot; 2],
     last_update: std::time::Instant,
     failed_read: bool,
     to_brain: ToBrain,
+    recorder: Option<Recorder>,
 }
 
 impl Brain {
@@ -24,27 +93,38 @@ impl Brain {
         };
         log::info!("Connected to the brain");
 
-        let first = loop {
-            if let Ok(pkt) = mediator.try_read() {
-                break pkt.to_owned();
-            }
-        };
-
-        let second = loop {
-            if let Ok(pkt) = mediator.try_read() {
-                break pkt.to_owned();
+        // the brain takes a moment to start sending after connecting
+        let mut wait_for_packet = || loop {
+            if let Ok(pkt) = mediator.read_packet() {
+                break pkt;
             }
         };
+        let packets = [wait_for_packet(), wait_for_packet()];
+        Self::with_packets(mediator, packets)
+    }
+}
 
-        let packet_buffer = [first, second];
+impl<B: BrainIo> Brain<B> {
+    /// Builds a `Brain` on top of any packet source, reading the first two
+    /// packets straight away. Fails with the read error if they aren't
+    /// there, so a `MockBrain` needs them queued beforehand.
+    pub fn with_io(mut io: B) -> Result<(Self, Controller), robot_serial::Error> {
+        let first = io.read_packet()?;
+        let second = io.read_packet()?;
+        Ok(Self::with_packets(io, [first, second]))
+    }
+    /// Builds a `Brain` that starts as if `packet_buffer` had already been
+    /// read, in the order `with_io` reads them.
+    pub fn with_packets(io: B, packet_buffer: [ToRobot; 2]) -> (Self, Controller) {
         let controller = packet_buffer.clone().into();
         (
             Self {
-                mediator,
+                io,
                 packet_buffer,
-                last_update: std::time::Instant::now(),
+                last_update: clock::now(),
                 failed_read: false,
                 to_brain: ToBrain::default(),
+                recorder: None,
             },
             controller,
         )
@@ -52,10 +132,11 @@ impl Brain {
     pub fn update_state(&mut self, controller: &mut Controller) -> (ToRobot, bool) {
         controller.update_no_change();
         let mut matched_ok = false;
-        match self.mediator.try_read() {
+        match self.io.read_packet() {
             Ok(pkt) => {
                 self.failed_read = false;
-                self.packet_buffer[1] = pkt.to_owned();
+                Self::record(&mut self.recorder, |v| v.record_to_robot(&pkt));
+                self.packet_buffer[1] = pkt;
                 self.packet_buffer.swap(0, 1);
                 *controller = self.packet_buffer.clone().into();
                 matched_ok = true;
@@ -74,8 +155,47 @@ impl Brain {
         &mut self.to_brain
     }
     pub fn write_changes(&mut self) {
-        if let Err(e) = self.mediator.try_write(&self.to_brain) {
+        if let Err(e) = self.io.write_packet(&self.to_brain) {
             log::error!("Failed to write packet: {e}");
         }
+        Self::record(&mut self.recorder, |v| v.record_to_brain(&self.to_brain));
+    }
+    // a full log stops the recording rather than failing every packet
+    fn record(
+        recorder: &mut Option<Recorder>,
+        f: impl FnOnce(&mut Recorder) -> Result<(), RecordError>,
+    ) {
+        let Some(Err(e)) = recorder.as_mut().map(f) else {
+            return;
+        };
+        match e {
+            RecordError::LimitReached => {
+                log::warn!("Brain traffic log is full, no longer recording");
+                if let Some(Err(e)) = recorder.take().map(|mut v| v.flush()) {
+                    log::error!("Failed to flush brain traffic log: {e}");
+                }
+            }
+            e => log::error!("Failed to record packet: {e}"),
+        }
+    }
+    /// Logs every packet read and written from now on, replacing (and
+    /// flushing) any earlier recorder.
+    pub fn record_to(&mut self, recorder: Recorder) {
+        self.stop_recording();
+        self.recorder = Some(recorder);
+    }
+    /// Flushes and hands back the recorder, if there is one.
+    pub fn stop_recording(&mut self) -> Option<Recorder> {
+        let mut recorder = self.recorder.take()?;
+        if let Err(e) = recorder.flush() {
+            log::error!("Failed to flush brain traffic log: {e}");
+        }
+        Some(recorder)
+    }
+    pub fn io(&self) -> &B {
+        &self.io
+    }
+    pub fn io_mut(&mut self) -> &mut B {
+        &mut self.io
     }
 }
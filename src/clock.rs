@@ -0,0 +1,80 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Source of time for all control code. Everything that would otherwise
+/// call `Instant::now()` goes through `clock::now()` so the clock in use can
+/// be swapped for a manually advanced one in tests and simulation.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock, used unless another clock is installed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time so one
+/// handle can be installed while another is kept around to advance it.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    origin: Instant,
+    elapsed: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            elapsed: Rc::new(Cell::new(Duration::ZERO)),
+        }
+    }
+    pub fn advance(&self, dur: Duration) {
+        self.elapsed.set(self.elapsed.get() + dur);
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed.get()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed.get()
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Rc<dyn Clock>> = RefCell::new(Rc::new(MonotonicClock));
+}
+
+/// Installs `clock` for the current thread.
+pub fn set_clock<C: Clock + 'static>(clock: C) {
+    CLOCK.with(|c| *c.borrow_mut() = Rc::new(clock));
+}
+
+/// Goes back to the real monotonic clock for the current thread.
+pub fn reset_clock() {
+    set_clock(MonotonicClock);
+}
+
+pub fn now() -> Instant {
+    CLOCK.with(|c| c.borrow().now())
+}
+
+/// Time passed since `since` on the installed clock.
+pub fn elapsed(since: Instant) -> Duration {
+    now().saturating_duration_since(since)
+}
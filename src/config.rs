@@ -0,0 +1,266 @@
+use std::{collections::BTreeMap, path::Path};
+
+use robot_serial::protocol::MotorControl;
+use serde::Deserialize;
+
+use crate::{
+    drivebase::Drivebase,
+    imu::Imu,
+    latch::Latch,
+    pid::Pid,
+    ports::{Motor, PortError, PortRegistry, SmartPortHandle},
+    ramsete::Ramsete,
+};
+
+/// Description of a robot's wiring and tuning, loaded from a TOML file
+/// (see `robots/small.toml`) so rewiring doesn't need a rebuild.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotConfig {
+    pub drivebase: DrivebaseConfig,
+    pub imu: ImuConfig,
+    #[serde(default)]
+    pub latches: BTreeMap<String, LatchConfig>,
+    #[serde(default)]
+    pub motors: BTreeMap<String, usize>,
+    pub angle_pid: PidConfig,
+    /// gains for driving a distance, output is power per mm of error
+    pub linear_pid: Option<PidConfig>,
+    pub ramsete: Option<RamseteConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DrivebaseConfig {
+    pub left: Vec<(usize, bool)>,
+    pub right: Vec<(usize, bool)>,
+    pub brakemode: BrakeMode,
+    pub radius: f64,
+    pub radians_to_mil: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrakeMode {
+    Brake,
+    Hold,
+    Coast,
+}
+
+impl From<BrakeMode> for MotorControl {
+    fn from(mode: BrakeMode) -> Self {
+        match mode {
+            BrakeMode::Brake => MotorControl::BrakeBrake,
+            BrakeMode::Hold => MotorControl::BrakeHold,
+            BrakeMode::Coast => MotorControl::BrakeCoast,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImuConfig {
+    pub port: usize,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LatchKind {
+    Air,
+    Motor,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LatchConfig {
+    pub kind: LatchKind,
+    pub port: usize,
+    #[serde(default)]
+    pub reversed: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PidConfig {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RamseteConfig {
+    pub beta: f64,
+    pub zeta: f64,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Port(PortError),
+    DrivebaseSize {
+        expected: usize,
+        left: usize,
+        right: usize,
+    },
+    MissingLatch(String),
+    MissingMotor(String),
+    MissingLinearPid,
+    MissingRamsete,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read robot config: {e}"),
+            Self::Parse(e) => write!(f, "failed to parse robot config: {e}"),
+            Self::Port(e) => write!(f, "invalid port: {e}"),
+            Self::DrivebaseSize {
+                expected,
+                left,
+                right,
+            } => write!(
+                f,
+                "drivebase expects {expected} motors per side but has {left} left and {right} right"
+            ),
+            Self::MissingLatch(name) => write!(f, "no latch named {name}"),
+            Self::MissingMotor(name) => write!(f, "no motor named {name}"),
+            Self::MissingLinearPid => write!(f, "no linear_pid section"),
+            Self::MissingRamsete => write!(f, "no ramsete section"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<PortError> for ConfigError {
+    fn from(e: PortError) -> Self {
+        Self::Port(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl RobotConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(s)?;
+        config.validate()?;
+        Ok(config)
+    }
+    /// Checks every port is in range and no port is used twice by claiming
+    /// everything from a scratch registry.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let ports = PortRegistry::new();
+        let drivebase = &self.drivebase;
+        for (port, _) in drivebase.left.iter().chain(&drivebase.right) {
+            ports.claim_smart(*port, "drivebase")?;
+        }
+        self.imu(&ports)?;
+        for name in self.latches.keys() {
+            self.latch(&ports, name)?;
+        }
+        for name in self.motors.keys() {
+            self.motor(&ports, name)?;
+        }
+        Ok(())
+    }
+    /// Checks a robot can be built from the config: `N` drivebase motors a
+    /// side and every named latch and motor present. `validate` can't know
+    /// this as it depends on the robot using the config.
+    pub fn require<const N: usize>(
+        &self,
+        latches: &[&str],
+        motors: &[&str],
+    ) -> Result<(), ConfigError> {
+        let cfg = &self.drivebase;
+        if cfg.left.len() != N || cfg.right.len() != N {
+            return Err(ConfigError::DrivebaseSize {
+                expected: N,
+                left: cfg.left.len(),
+                right: cfg.right.len(),
+            });
+        }
+        if let Some(name) = latches.iter().find(|v| !self.latches.contains_key(**v)) {
+            return Err(ConfigError::MissingLatch(name.to_string()));
+        }
+        if let Some(name) = motors.iter().find(|v| !self.motors.contains_key(**v)) {
+            return Err(ConfigError::MissingMotor(name.to_string()));
+        }
+        Ok(())
+    }
+    pub fn drivebase<const N: usize>(
+        &self,
+        ports: &PortRegistry,
+    ) -> Result<Drivebase<N>, ConfigError> {
+        let cfg = &self.drivebase;
+        let claim_side = |side: &[(usize, bool)]| -> Result<[Motor; N], _> {
+            let handles = side
+                .iter()
+                .map(|(port, rev)| Ok(ports.claim_smart(*port, "drivebase")?.reversed(*rev)))
+                .collect::<Result<Vec<_>, ConfigError>>()?;
+            handles.try_into().map_err(|_| ConfigError::DrivebaseSize {
+                expected: N,
+                left: cfg.left.len(),
+                right: cfg.right.len(),
+            })
+        };
+        Ok(Drivebase::new(
+            claim_side(&cfg.left)?,
+            claim_side(&cfg.right)?,
+            cfg.brakemode.into(),
+            cfg.radius,
+            cfg.radians_to_mil,
+        ))
+    }
+    pub fn imu(&self, ports: &PortRegistry) -> Result<Imu, ConfigError> {
+        Ok(Imu::new(ports.claim_smart(self.imu.port, "imu")?))
+    }
+    pub fn latch(&self, ports: &PortRegistry, name: &str) -> Result<Latch, ConfigError> {
+        let cfg = self
+            .latches
+            .get(name)
+            .ok_or_else(|| ConfigError::MissingLatch(name.to_owned()))?;
+        let owner = format!("latch {name}");
+        Ok(match cfg.kind {
+            LatchKind::Air => Latch::new_air(ports.claim_adi(cfg.port, &owner)?, cfg.reversed),
+            LatchKind::Motor => {
+                Latch::new_motor(ports.claim_smart(cfg.port, &owner)?, cfg.reversed)
+            }
+        })
+    }
+    pub fn motor(&self, ports: &PortRegistry, name: &str) -> Result<SmartPortHandle, ConfigError> {
+        let port = self
+            .motors
+            .get(name)
+            .ok_or_else(|| ConfigError::MissingMotor(name.to_owned()))?;
+        Ok(ports.claim_smart(*port, &format!("motor {name}"))?)
+    }
+    pub fn angle_pid(&self) -> Pid {
+        Pid::new(self.angle_pid.kp, self.angle_pid.ki, self.angle_pid.kd)
+    }
+    pub fn linear_pid(&self) -> Result<Pid, ConfigError> {
+        let cfg = self
+            .linear_pid
+            .as_ref()
+            .ok_or(ConfigError::MissingLinearPid)?;
+        Ok(Pid::new(cfg.kp, cfg.ki, cfg.kd))
+    }
+    pub fn ramsete(&self) -> Result<Ramsete, ConfigError> {
+        let cfg = self.ramsete.as_ref().ok_or(ConfigError::MissingRamsete)?;
+        Ok(Ramsete::new(cfg.beta, cfg.zeta))
+    }
+}
@@ -1,12 +1,12 @@
 use std::f64::consts::TAU;
 
-use robot_serial::protocol::{EncoderState, MotorControl, ToBrain, ToRobot};
+use robot_serial::protocol::{MotorControl, ToBrain, ToRobot};
 
-use crate::vec::Vec2;
+use crate::{ports::Motor, vec::Vec2};
 
 pub struct Drivebase<const N: usize> {
-    left: [(usize, bool); N],
-    right: [(usize, bool); N],
+    left: [Motor; N],
+    right: [Motor; N],
     brakemode: MotorControl,
     side_distances: Vec2,
     radius: f64,
@@ -15,15 +15,12 @@ pub struct Drivebase<const N: usize> {
 
 impl<const N: usize> Drivebase<N> {
     pub fn new(
-        left: [(usize, bool); N],
-        right: [(usize, bool); N],
+        left: [Motor; N],
+        right: [Motor; N],
         brakemode: MotorControl,
         radius: f64,
         radians_to_mil: f64,
     ) -> Self {
-        for (i, _) in left.iter().chain(right.iter()) {
-            assert!((1..=21).contains(i));
-        }
         Self {
             left,
             right,
@@ -34,23 +31,18 @@ impl<const N: usize> Drivebase<N> {
         }
     }
     pub fn write_voltage(&self, left: f64, right: f64, brain_pkt: &mut ToBrain) {
-        let map_voltage = |power: f64, rev: bool| -> MotorControl {
+        let map_voltage = |power: f64| -> MotorControl {
             if power == 0.0 {
                 return self.brakemode;
             }
-            let power = (power * 12.0).clamp(-12.0, 12.0);
-            if rev {
-                MotorControl::Voltage(-power)
-            } else {
-                MotorControl::Voltage(power)
-            }
+            MotorControl::Voltage((power * 12.0).clamp(-12.0, 12.0))
         };
 
-        for (idx, rev) in &self.left {
-            brain_pkt.set_motors[*idx - 1] = map_voltage(left, *rev);
+        for motor in &self.left {
+            motor.set(brain_pkt, map_voltage(left));
         }
-        for (idx, rev) in &self.right {
-            brain_pkt.set_motors[*idx - 1] = map_voltage(right, *rev);
+        for motor in &self.right {
+            motor.set(brain_pkt, map_voltage(right));
         }
     }
     pub fn write_powers(&self, forward: f64, rotate: f64, brain_pkt: &mut ToBrain) {
@@ -66,7 +58,7 @@ impl<const N: usize> Drivebase<N> {
         let left = linear_angular - angular;
         let right = linear_angular + angular;
 
-        let map_rpm = |angular_vel: f64, rev: bool| -> MotorControl {
+        let map_rpm = |angular_vel: f64| -> MotorControl {
             if angular_vel == 0.0 {
                 return self.brakemode;
             }
@@ -75,30 +67,40 @@ impl<const N: usize> Drivebase<N> {
             // TODO: validate if gearing is taken care of from above?
             let target_rpm = angular_vel / TAU * 60.0;
 
-            if rev {
-                MotorControl::Velocity(-target_rpm as i32)
-            } else {
-                MotorControl::Velocity(target_rpm as i32)
-            }
+            MotorControl::Velocity(target_rpm as i32)
         };
 
-        for (idx, rev) in &self.left {
-            brain_pkt.set_motors[*idx - 1] = (map_rpm(left, *rev));
+        for motor in &self.left {
+            motor.set(brain_pkt, map_rpm(left));
         }
-        for (idx, rev) in &self.right {
-            brain_pkt.set_motors[*idx - 1] = (map_rpm(right, *rev));
+        for motor in &self.right {
+            motor.set(brain_pkt, map_rpm(right));
         }
         // d = ang * pi
     }
+    /// Runs each side at a wheel surface speed in mm/s using the motors'
+    /// velocity control.
+    pub fn write_wheel_velocities(&self, left: f64, right: f64, brain_pkt: &mut ToBrain) {
+        let map_rpm = |mm_per_sec: f64| -> MotorControl {
+            if mm_per_sec == 0.0 {
+                return self.brakemode;
+            }
+            let rad_per_sec = mm_per_sec / self.radians_to_mil;
+            MotorControl::Velocity((rad_per_sec / TAU * 60.0).round() as i32)
+        };
+
+        for motor in &self.left {
+            motor.set(brain_pkt, map_rpm(left));
+        }
+        for motor in &self.right {
+            motor.set(brain_pkt, map_rpm(right));
+        }
+    }
     pub fn update(&mut self, pkt: &ToRobot) -> Vec2 {
-        let get_dist = |motors: &[(usize, bool); N]| -> Option<f64> {
+        let get_dist = |motors: &[Motor; N]| -> Option<f64> {
             let mut sum = 0.0;
-            for (port, rev) in motors {
-                let mul = if *rev { -1.0 } else { 1.0 };
-                let EncoderState::Radians(radians) = pkt.encoder_state[port - 1] else {
-                    return None;
-                };
-                sum += radians * self.radians_to_mil * mul;
+            for motor in motors {
+                sum += motor.radians(pkt)? * self.radians_to_mil;
             }
             Some(sum / N as f64)
         };
@@ -112,6 +114,8 @@ impl<const N: usize> Drivebase<N> {
     pub fn side_distances(&self) -> Vec2 {
         self.side_distances
     }
+    /// Half the track width in mm, from the centre of the robot to the
+    /// wheels on either side.
     pub fn radius(&self) -> f64 {
         self.radius
     }
@@ -1,13 +1,15 @@
 use robot_serial::protocol::*;
+
+use crate::ports::SmartPortHandle;
+
 pub struct Imu {
-    port: usize,
+    port: SmartPortHandle,
     original_heading: Option<f64>,
     heading: f64,
 }
 
 impl Imu {
-    pub fn new(port: usize) -> Self {
-        assert!((1..=21).contains(&port));
+    pub fn new(port: SmartPortHandle) -> Self {
         Self {
             port,
             original_heading: None,
@@ -21,7 +23,7 @@ impl Imu {
         self.heading
     }
     pub fn update(&mut self, pkt: &ToRobot) {
-        let ImuState::State { z_rotation, .. } = pkt.imu_state[self.port - 1] else {
+        let ImuState::State { z_rotation, .. } = pkt.imu_state[self.port.port().index()] else {
             return;
         };
         let z_rotation = -z_rotation;
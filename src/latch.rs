@@ -5,17 +5,21 @@ use std::{
 
 use robot_serial::protocol::{AdiPortState, ConfigureAdiPort, MotorControl, ToBrain};
 
-use crate::path::PathSegment;
+use crate::{
+    clock,
+    path::PathSegment,
+    ports::{AdiPortHandle, SmartPortHandle},
+};
 
 #[derive(Debug, Clone)]
 pub enum Latch {
     Air {
-        port: usize,
+        port: AdiPortHandle,
         active: bool,
         rev: bool,
     },
     Motor {
-        port: usize,
+        port: SmartPortHandle,
         last_change: Instant,
         active: bool,
         rev: bool,
@@ -25,19 +29,17 @@ pub enum Latch {
 impl Latch {
     const MOTOR_TIME: Duration = Duration::from_millis(50);
     const VOLTAGE: f64 = 3.0;
-    pub fn new_air(port: usize, rev: bool) -> Self {
-        assert!((1..=8).contains(&port));
+    pub fn new_air(port: AdiPortHandle, rev: bool) -> Self {
         Self::Air {
             port,
             active: false,
             rev,
         }
     }
-    pub fn new_motor(port: usize, rev: bool) -> Self {
-        assert!((1..=21).contains(&port));
+    pub fn new_motor(port: SmartPortHandle, rev: bool) -> Self {
         Self::Motor {
             port,
-            last_change: Instant::now(),
+            last_change: clock::now(),
             active: false,
             rev,
         }
@@ -54,7 +56,7 @@ impl Latch {
                 ..
             } => {
                 *active = !*rev;
-                *last_change = Instant::now();
+                *last_change = clock::now();
             }
         }
     }
@@ -70,7 +72,7 @@ impl Latch {
                 ..
             } => {
                 *active = *rev;
-                *last_change = Instant::now();
+                *last_change = clock::now();
             }
         }
     }
@@ -85,33 +87,34 @@ impl Latch {
                 ..
             } => {
                 *active = !*active;
-                *last_change = Instant::now();
+                *last_change = clock::now();
             }
         }
     }
     pub fn write_pkt(&self, pkt: &mut ToBrain) {
         match self {
-            Self::Air { port, active, rev } => {
-                pkt.set_triports[port - 1] = if active != rev {
+            Self::Air { port, active, rev } => port.set_triport(
+                pkt,
+                if active != rev {
                     ConfigureAdiPort::DigitalHigh
                 } else {
                     ConfigureAdiPort::DigitalLow
-                }
-            }
+                },
+            ),
             Self::Motor {
                 port,
                 last_change,
                 active,
                 rev,
             } => {
-                if last_change.elapsed() > Self::MOTOR_TIME {
-                    pkt.set_motors[port - 1] = MotorControl::BrakeBrake;
+                if clock::elapsed(*last_change) > Self::MOTOR_TIME {
+                    port.set_motor(pkt, MotorControl::BrakeBrake);
                     return;
                 }
                 if active != rev {
-                    pkt.set_motors[port - 1] = MotorControl::Voltage(Self::VOLTAGE);
+                    port.set_motor(pkt, MotorControl::Voltage(Self::VOLTAGE));
                 } else {
-                    pkt.set_motors[port - 1] = MotorControl::Voltage(-Self::VOLTAGE);
+                    port.set_motor(pkt, MotorControl::Voltage(-Self::VOLTAGE));
                 }
             }
         }
@@ -155,7 +158,7 @@ impl PathSegment for LatchAction {
         }
         self.latch.write_pkt(pkt);
         self.wrote = true;
-        crate::path::PathOutput::Voltages(crate::vec::Vec2::ZERO)
+        crate::path::PathOutput::Idle
     }
 
     fn end_follow<'a>(
@@ -169,4 +172,7 @@ impl PathSegment for LatchAction {
             None
         }
     }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new(self.latch.clone(), self.release))
+    }
 }
@@ -0,0 +1,22 @@
+pub mod auton;
+pub mod brain;
+pub mod clock;
+pub mod config;
+pub mod controller;
+pub mod drivebase;
+pub mod imu;
+pub mod latch;
+pub mod modifier_path;
+pub mod odometry;
+pub mod path;
+pub mod pid;
+pub mod ports;
+pub mod ramsete;
+pub mod record;
+pub mod script;
+pub mod shaking_motor;
+pub mod sim;
+pub mod spline;
+pub mod trace;
+pub mod trajectory;
+pub mod vec;
@@ -1,7 +1,13 @@
-use robot_serial::protocol::ToBrain;
+use std::{rc::Rc, time::Duration};
 
-use crate::{odometry::Odom, path::*, pid::Pid, vec::Vec2};
+use robot_serial::protocol::{EncoderState, ToBrain, ToRobot};
 
+use crate::{
+    clock, odometry::Odom, path::*, pid::Pid, ports::SmartPort, trace::EndReason, vec::Vec2,
+};
+
+/// Does nothing and never ends, leaving the drivetrain idle. Wrap it in a
+/// `TimedSegment` to wait.
 #[derive(Debug, Clone, Copy)]
 pub struct Nop {}
 
@@ -11,7 +17,7 @@ impl PathSegment for Nop {
     }
     fn start(&mut self, _: &Odom, _: &mut Pid, pkt: &mut ToBrain) {}
     fn follow(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
-        PathOutput::Voltages(Vec2::ZERO)
+        PathOutput::Idle
     }
     fn end_follow<'a>(
         &mut self,
@@ -29,17 +35,19 @@ impl PathSegment for Nop {
 pub struct RepeatSegment {
     max_count: usize,
     count: usize,
-    ref_seg: Box<dyn PathSegment>,
+    // a Path so segment start and transform don't need handling here
+    ref_seg: Path,
     current_seg: Box<dyn PathSegment>,
 }
 
 impl RepeatSegment {
     pub fn new(path: Box<dyn PathSegment>, max_count: usize) -> Self {
+        let ref_seg: Path = path.into();
         Self {
             max_count,
             count: 0,
-            current_seg: path.boxed_clone(),
-            ref_seg: path.boxed_clone(),
+            current_seg: ref_seg.boxed_clone(),
+            ref_seg,
         }
     }
 }
@@ -48,11 +56,7 @@ impl PathSegment for RepeatSegment {
     fn finished_transform(&self) -> bool {
         true
     }
-    // This assures self.ref_seg is of type "Path" (to avoid handling
-    // segment start and transform code)
     fn start(&mut self, _: &Odom, _: &mut Pid, pkt: &mut ToBrain) {
-        let a: Box<Path> = Box::new(self.ref_seg.boxed_clone().into());
-        self.ref_seg = a;
         self.current_seg = self.ref_seg.boxed_clone();
     }
     fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
@@ -65,7 +69,10 @@ impl PathSegment for RepeatSegment {
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
         let ret = self.current_seg.end_follow(odom, pkt)?;
 
-        if ret.is_empty() && self.count != self.max_count {
+        if ret.is_empty()
+            && self.count != self.max_count
+            && !self.current_seg.outcome().is_failure()
+        {
             self.count += 1;
             self.current_seg = self.ref_seg.boxed_clone();
             return None;
@@ -73,12 +80,21 @@ impl PathSegment for RepeatSegment {
 
         Some(ret)
     }
+    fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        self.current_seg.abrupt_end(odom, pkt);
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.current_seg.shift_time(by);
+    }
+    fn outcome(&self) -> Outcome {
+        self.current_seg.outcome()
+    }
     fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
         Box::new(Self {
             max_count: self.max_count,
             count: 0,
             current_seg: self.ref_seg.boxed_clone(),
-            ref_seg: self.ref_seg.boxed_clone(),
+            ref_seg: self.ref_seg.clone(),
         })
     }
 }
@@ -126,8 +142,249 @@ impl PathSegment for WhileSegment {
         self.main.abrupt_end(odom, pkt);
         self.secondary.abrupt_end(odom, pkt);
     }
+    fn outcome(&self) -> Outcome {
+        self.main.outcome()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.main.shift_time(by);
+        self.secondary.shift_time(by);
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new(
+            self.main.clone(),
+            self.secondary.clone(),
+            self.end_secondary,
+        ))
+    }
+}
+
+/// When a `ParallelSegment` is considered finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParallelEnd {
+    /// end as soon as any child ends
+    Race,
+    /// end once every child has ended
+    All,
+    /// end when the child at this index ends
+    Deadline(usize),
+}
+
+/// Runs several paths at the same time.
+///
+/// Children are in priority order: each tick the drivetrain is given the
+/// output of the first child that isn't `PathOutput::Idle`, later children
+/// still run but their drivetrain output is dropped. A child holding still
+/// with zero voltages, such as a settled `TurnTo` or a `WaitUntil`, keeps
+/// the drivetrain.
+///
+/// The first child uses the shared angle pid, the others get their own
+/// copy of it when the segment starts so turning children don't overwrite
+/// each other's target.
+///
+/// Children that are still running when the segment ends are ended abruptly.
+/// The outcome is the first unsuccessful one of the children that finished
+/// on their own.
+#[derive(Debug)]
+pub struct ParallelSegment {
+    children: Vec<Path>,
+    ended: Vec<bool>,
+    outcomes: Vec<Option<Outcome>>,
+    end: ParallelEnd,
+    // for every child but the first
+    angle_pids: Vec<Pid>,
+}
+
+impl ParallelSegment {
+    pub fn new(children: Vec<Path>, end: ParallelEnd) -> Self {
+        if let ParallelEnd::Deadline(idx) = end {
+            assert!(idx < children.len(), "deadline child {idx} doesn't exist");
+        }
+        Self {
+            ended: vec![false; children.len()],
+            outcomes: vec![None; children.len()],
+            children,
+            end,
+            angle_pids: Vec::new(),
+        }
+    }
+    pub fn race(children: Vec<Path>) -> Self {
+        Self::new(children, ParallelEnd::Race)
+    }
+    pub fn all(children: Vec<Path>) -> Self {
+        Self::new(children, ParallelEnd::All)
+    }
+    /// The deadline path is given drivetrain priority over the others.
+    pub fn deadline(deadline: Path, others: Vec<Path>) -> Self {
+        let mut children = vec![deadline];
+        children.extend(others);
+        Self::new(children, ParallelEnd::Deadline(0))
+    }
+    fn finished(&self) -> bool {
+        match self.end {
+            ParallelEnd::Race => self.ended.iter().any(|v| *v),
+            ParallelEnd::All => self.ended.iter().all(|v| *v),
+            ParallelEnd::Deadline(idx) => self.ended[idx],
+        }
+    }
+    fn end_running(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        for (child, ended) in self.children.iter_mut().zip(&mut self.ended) {
+            if !*ended {
+                child.abrupt_end(odom, pkt);
+                *ended = true;
+            }
+        }
+    }
+}
+
+impl PathSegment for ParallelSegment {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, _: &Odom, angle_pid: &mut Pid, _: &mut ToBrain) {
+        self.ended.fill(false);
+        self.outcomes.fill(None);
+        self.angle_pids = vec![angle_pid.clone(); self.children.len().saturating_sub(1)];
+    }
+    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
+        let mut output = None;
+        let pids = std::iter::once(angle_pid).chain(&mut self.angle_pids);
+        for ((child, ended), pid) in self.children.iter_mut().zip(&self.ended).zip(pids) {
+            if *ended {
+                continue;
+            }
+            let child_output = child.follow(odom, pid, pkt);
+            if output.is_none() && !child_output.is_idle() {
+                output = Some(child_output);
+            }
+        }
+        output.unwrap_or(PathOutput::Idle)
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        pkt: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        for (i, child) in self.children.iter_mut().enumerate() {
+            if !self.ended[i] && child.end_follow(odom, pkt).is_some() {
+                self.ended[i] = true;
+                self.outcomes[i] = Some(child.outcome());
+            }
+        }
+        if self.finished() {
+            self.end_running(odom, pkt);
+            return Some(Vec::new());
+        }
+        None
+    }
+    fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        self.end_running(odom, pkt);
+    }
+    fn outcome(&self) -> Outcome {
+        self.outcomes
+            .iter()
+            .flatten()
+            .find(|v| !v.is_success())
+            .cloned()
+            .unwrap_or(Outcome::Succeeded)
+    }
+    fn shift_time(&mut self, by: Duration) {
+        for child in &mut self.children {
+            child.shift_time(by);
+        }
+        for pid in &mut self.angle_pids {
+            pid.shift_time(by);
+        }
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new(self.children.clone(), self.end))
+    }
+}
+
+/// Picks which path to run from the robot state. The packet is the latest
+/// one seen by `Odom` and is `None` before the first update.
+pub type Selector = Rc<dyn Fn(&Odom, Option<&ToRobot>) -> usize>;
+
+/// Condition on the robot state, see `Check`.
+pub type Predicate = Rc<dyn Fn(&Odom, Option<&ToRobot>) -> bool>;
+
+/// Chooses one of several paths when it is reached, by replacing itself with
+/// the chosen path through `transform`.
+pub struct SelectSegment {
+    selector: Selector,
+    options: Vec<Path>,
+}
+
+impl SelectSegment {
+    /// `selector` returns the index of the option to run, an index past the
+    /// end runs nothing.
+    pub fn new<F>(selector: F, options: Vec<Path>) -> Self
+    where
+        F: Fn(&Odom, Option<&ToRobot>) -> usize + 'static,
+    {
+        Self {
+            selector: Rc::new(selector),
+            options,
+        }
+    }
+    pub fn if_else<F>(predicate: F, then: Path, otherwise: Path) -> Self
+    where
+        F: Fn(&Odom, Option<&ToRobot>) -> bool + 'static,
+    {
+        Self::new(
+            move |odom, pkt| if predicate(odom, pkt) { 0 } else { 1 },
+            vec![then, otherwise],
+        )
+    }
+}
+
+/// Predicate for `SelectSegment::if_else` and `WaitUntil` that holds while
+/// the robot is inside the axis aligned box between `min` and `max`.
+pub fn in_region(min: Vec2, max: Vec2) -> impl Fn(&Odom, Option<&ToRobot>) -> bool {
+    move |odom, _| {
+        let pos = odom.pos();
+        (min.x..=max.x).contains(&pos.x) && (min.y..=max.y).contains(&pos.y)
+    }
+}
+
+impl std::fmt::Debug for SelectSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectSegment")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PathSegment for SelectSegment {
+    fn transform<'a>(mut self: Box<Self>, odom: &Odom) -> Vec<Box<dyn PathSegment + 'a>> {
+        let idx = (self.selector)(odom, odom.last_pkt());
+        if idx >= self.options.len() {
+            log::warn!("SelectSegment chose option {idx} which doesn't exist, skipping");
+            return Vec::new();
+        }
+        log::info!("SelectSegment chose option {idx}");
+        vec![Box::new(self.options.swap_remove(idx))]
+    }
+    fn finished_transform(&self) -> bool {
+        false
+    }
+    fn start(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) {
+        unreachable!("SelectSegment is always transformed before it starts");
+    }
+    fn follow(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        unreachable!("SelectSegment is always transformed before it starts");
+    }
+    fn end_follow<'a>(
+        &mut self,
+        _: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        unreachable!("SelectSegment is always transformed before it starts");
+    }
     fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
-        todo!()
+        Box::new(Self {
+            selector: self.selector.clone(),
+            options: self.options.clone(),
+        })
     }
 }
 
@@ -136,6 +393,7 @@ pub struct TimedSegment {
     seg: Box<dyn PathSegment>,
     dur: std::time::Duration,
     start: std::time::Instant,
+    timed_out: bool,
 }
 
 impl TimedSegment {
@@ -143,7 +401,8 @@ impl TimedSegment {
         Self {
             seg,
             dur,
-            start: std::time::Instant::now(),
+            start: clock::now(),
+            timed_out: false,
         }
     }
 }
@@ -156,7 +415,7 @@ impl PathSegment for TimedSegment {
         self.seg.finished_transform()
     }
     fn start(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) {
-        self.start = std::time::Instant::now();
+        self.start = clock::now();
         self.seg.start(odom, angle_pid, pkt);
     }
     fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
@@ -167,17 +426,336 @@ impl PathSegment for TimedSegment {
         odom: &Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
-        if self.start.elapsed() > self.dur {
+        if clock::elapsed(self.start) > self.dur {
             self.seg.abrupt_end(odom, pkt);
+            self.timed_out = true;
             return Some(Vec::new());
         }
         self.seg.end_follow(odom, pkt)
     }
+    fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        self.seg.abrupt_end(odom, pkt);
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.start += by;
+        self.seg.shift_time(by);
+    }
+    // running out of time is what a TimedSegment is for, so it isn't a
+    // failure, traces still show it as timed out
+    fn outcome(&self) -> Outcome {
+        if self.timed_out {
+            Outcome::Succeeded
+        } else {
+            self.seg.outcome()
+        }
+    }
+    fn end_reason(&self) -> EndReason {
+        if self.timed_out {
+            EndReason::TimedOut
+        } else {
+            self.seg.end_reason()
+        }
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new(self.seg.as_ref().boxed_clone(), self.dur))
+    }
+}
+
+/// Gives a segment a name that shows up in traces.
+#[derive(Debug)]
+pub struct Labeled {
+    label: String,
+    seg: Box<dyn PathSegment>,
+}
+
+impl Labeled {
+    pub fn new(label: &str, seg: Box<dyn PathSegment>) -> Self {
+        Self {
+            label: label.to_owned(),
+            seg,
+        }
+    }
+}
+
+impl PathSegment for Labeled {
+    // keeps the label on whatever the segment turns into
+    fn transform<'a>(self: Box<Self>, odom: &Odom) -> Vec<Box<dyn PathSegment + 'a>> {
+        let label = self.label;
+        self.seg
+            .transform(odom)
+            .into_iter()
+            .map(|seg| -> Box<dyn PathSegment> { Box::new(Labeled::new(&label, seg)) })
+            .collect()
+    }
+    fn finished_transform(&self) -> bool {
+        self.seg.finished_transform()
+    }
+    fn start(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) {
+        self.seg.start(odom, angle_pid, pkt);
+    }
+    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
+        self.seg.follow(odom, angle_pid, pkt)
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        pkt: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        self.seg.end_follow(odom, pkt)
+    }
+    fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        self.seg.abrupt_end(odom, pkt);
+    }
+    fn outcome(&self) -> Outcome {
+        self.seg.outcome()
+    }
+    fn end_reason(&self) -> EndReason {
+        self.seg.end_reason()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.seg.shift_time(by);
+    }
+    fn name(&self) -> &'static str {
+        self.seg.name()
+    }
+    fn label(&self) -> Option<&str> {
+        Some(&self.label)
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new(&self.label, self.seg.as_ref().boxed_clone()))
+    }
+}
+
+/// Runs `body` and, if it doesn't succeed, runs `recovery` in its place.
+/// The outcome is the body's on success and the recovery's otherwise. A
+/// failure stops the body straight away, a timeout only once the body has
+/// run to the end.
+#[derive(Debug)]
+pub struct TrySegment {
+    body: Path,
+    recovery: Path,
+    recovering: bool,
+    outcome: Outcome,
+}
+
+impl TrySegment {
+    pub fn new(body: Path, recovery: Path) -> Self {
+        Self {
+            body,
+            recovery,
+            recovering: false,
+            outcome: Outcome::Succeeded,
+        }
+    }
+}
+
+impl PathSegment for TrySegment {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) {}
+    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
+        if self.recovering {
+            self.recovery.follow(odom, angle_pid, pkt)
+        } else {
+            self.body.follow(odom, angle_pid, pkt)
+        }
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        pkt: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        if self.recovering {
+            self.recovery.end_follow(odom, pkt)?;
+            self.outcome = self.recovery.outcome();
+            return Some(Vec::new());
+        }
+        self.body.end_follow(odom, pkt)?;
+        let outcome = self.body.outcome();
+        if outcome.is_success() {
+            self.outcome = outcome;
+            return Some(Vec::new());
+        }
+        log::warn!("TrySegment body {outcome}, running recovery");
+        self.recovering = true;
+        None
+    }
+    fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        if self.recovering {
+            self.recovery.abrupt_end(odom, pkt);
+        } else {
+            self.body.abrupt_end(odom, pkt);
+        }
+    }
+    fn shift_time(&mut self, by: Duration) {
+        if self.recovering {
+            self.recovery.shift_time(by);
+        } else {
+            self.body.shift_time(by);
+        }
+    }
+    fn outcome(&self) -> Outcome {
+        self.outcome.clone()
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new(self.body.clone(), self.recovery.clone()))
+    }
+}
+
+/// Ends straight away, failing with `reason` unless `predicate` holds. Put
+/// it after a step to turn a sensor reading into a failure that
+/// `TrySegment` can recover from.
+pub struct Check {
+    predicate: Predicate,
+    reason: String,
+    outcome: Outcome,
+}
+
+impl Check {
+    pub fn new<F>(reason: &str, predicate: F) -> Self
+    where
+        F: Fn(&Odom, Option<&ToRobot>) -> bool + 'static,
+    {
+        Self {
+            predicate: Rc::new(predicate),
+            reason: reason.to_owned(),
+            outcome: Outcome::Succeeded,
+        }
+    }
+}
+
+impl std::fmt::Debug for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Check")
+            .field("reason", &self.reason)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PathSegment for Check {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, odom: &Odom, _: &mut Pid, _: &mut ToBrain) {
+        self.outcome = if (self.predicate)(odom, odom.last_pkt()) {
+            Outcome::Succeeded
+        } else {
+            Outcome::Failed(self.reason.clone())
+        };
+    }
+    fn follow(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        PathOutput::Voltages(Vec2::ZERO)
+    }
+    fn end_follow<'a>(
+        &mut self,
+        _: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        Some(Vec::new())
+    }
+    fn outcome(&self) -> Outcome {
+        self.outcome.clone()
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self {
+            predicate: self.predicate.clone(),
+            reason: self.reason.clone(),
+            outcome: Outcome::Succeeded,
+        })
+    }
+}
+
+/// Waits until `predicate` holds, or gives up with `Outcome::TimedOut` after
+/// the timeout if one is set.
+pub struct WaitUntil {
+    predicate: Predicate,
+    timeout: Option<Duration>,
+    start: std::time::Instant,
+    outcome: Option<Outcome>,
+}
+
+impl WaitUntil {
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&Odom, Option<&ToRobot>) -> bool + 'static,
+    {
+        Self {
+            predicate: Rc::new(predicate),
+            timeout: None,
+            start: clock::now(),
+            outcome: None,
+        }
+    }
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// Predicate for `WaitUntil` that holds once the encoder on `port` has gone
+/// past `radians` in the direction of travel from zero.
+pub fn encoder_past(port: SmartPort, radians: f64) -> impl Fn(&Odom, Option<&ToRobot>) -> bool {
+    move |_, pkt| {
+        let Some(&EncoderState::Radians(v)) = pkt.map(|pkt| &pkt.encoder_state[port.index()])
+        else {
+            return false;
+        };
+        if radians < 0.0 {
+            v <= radians
+        } else {
+            v >= radians
+        }
+    }
+}
+
+impl std::fmt::Debug for WaitUntil {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WaitUntil")
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PathSegment for WaitUntil {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) {
+        self.start = clock::now();
+        self.outcome = None;
+    }
+    fn follow(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        PathOutput::Voltages(Vec2::ZERO)
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        if (self.predicate)(odom, odom.last_pkt()) {
+            self.outcome = Some(Outcome::Succeeded);
+        } else if self
+            .timeout
+            .is_some_and(|timeout| clock::elapsed(self.start) > timeout)
+        {
+            log::warn!("WaitUntil timed out after {:?}", self.timeout);
+            self.outcome = Some(Outcome::TimedOut);
+        }
+        self.outcome.as_ref().map(|_| Vec::new())
+    }
+    fn outcome(&self) -> Outcome {
+        self.outcome.clone().unwrap_or(Outcome::Succeeded)
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.start += by;
+    }
     fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
         Box::new(Self {
-            seg: self.seg.as_ref().boxed_clone(),
-            dur: self.dur,
-            start: self.start,
+            predicate: self.predicate.clone(),
+            timeout: self.timeout,
+            start: clock::now(),
+            outcome: None,
         })
     }
 }
@@ -1,4 +1,4 @@
-use crate::{drivebase::Drivebase, imu::Imu, vec::Vec2};
+use crate::{clock, drivebase::Drivebase, imu::Imu, vec::Vec2};
 use robot_serial::protocol::ToRobot;
 use std::time::{Duration, Instant};
 pub struct Odom {
@@ -28,13 +28,13 @@ impl Odom {
             pos: [0.0; 2].into(),
             heading,
             last_distances: drivebase.side_distances(),
-            last_update: Instant::now(),
+            last_update: clock::now(),
             velocity: 0.0,
             last_pkt: None,
         }
     }
     pub fn update<const N: usize>(&mut self, imu: &Imu, drivebase: &Drivebase<N>, pkt: &ToRobot) {
-        if self.last_update.elapsed() < Self::UPDATE_RATE {
+        if clock::elapsed(self.last_update) < Self::UPDATE_RATE {
             return;
         }
         self.last_pkt = Some(pkt.clone());
@@ -81,11 +81,11 @@ impl Odom {
         self.pos[0] += global_dx;
         self.pos[1] += global_dy;
         self.velocity = (global_dy.powi(2) + global_dx.powi(2)).sqrt()
-            / self.last_update.elapsed().as_secs_f64();
+            / clock::elapsed(self.last_update).as_secs_f64();
 
         self.last_distances = lr;
         self.heading = theta;
-        self.last_update = Instant::now();
+        self.last_update = clock::now();
     }
     pub fn pos(&self) -> Vec2 {
         self.start_pos + self.pos
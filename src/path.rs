@@ -1,40 +1,118 @@
 use robot_serial::protocol::{MotorControl, ToBrain};
 
 use crate::modifier_path::TimedSegment;
-use crate::ramsete::Ramsete;
-use crate::{odometry::Odom, pid::Pid, vec::Vec2};
+use crate::ramsete::{Ramsete, TrajectoryGains};
+use crate::trace::{self, EndReason};
+use crate::trajectory::Trajectory;
+use crate::{
+    clock, drivebase::Drivebase, odometry::Odom, pid::Pid, ports::SmartPortHandle, vec::Vec2,
+};
 use std::collections::VecDeque;
 use std::f64::consts::{PI, TAU};
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Copy, Clone)]
 pub enum PathOutput {
     Voltages(Vec2),
     LinearAngularVelocity(Vec2),
+    /// left and right wheel speeds in mm/s
+    WheelVelocities(Vec2),
     SwitchToDriver,
+    /// Leaves the drivetrain to whatever else is running, returned by
+    /// segments that only run mechanisms. With nothing else running the
+    /// drivetrain is stopped. Holding still on purpose is `Voltages` of zero.
+    Idle,
+}
+
+impl PathOutput {
+    pub fn is_idle(&self) -> bool {
+        matches!(self, Self::Idle)
+    }
+}
+
+/// How well a segment did what it was meant to. Only `Failed` stops the
+/// `Path` it's in; a timeout is logged and the path carries on, but still
+/// reports it so a `TrySegment` can recover once its body has finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded,
+    /// gave up after its own time limit without reaching its goal
+    TimedOut,
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        *self == Self::Succeeded
+    }
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Failed(_))
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Succeeded => write!(f, "succeeded"),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::Failed(reason) => write!(f, "failed: {reason}"),
+        }
+    }
 }
 
-#[derive(Debug)]
 pub struct Path {
     // this is a stack so the last element in
     // the vector is the first that will be run
     pub segments: VecDeque<Box<dyn PathSegment>>,
     pub current_segment: Option<Box<dyn PathSegment>>,
+    // untouched copies of the segments in the same order, used to clone and
+    // reset the path. Shared between clones so only `segments` is copied.
+    initial: Rc<[Box<dyn PathSegment>]>,
+    // trace record of the current segment
+    trace_id: Option<usize>,
+    outcome: Outcome,
+    paused_at: Option<Instant>,
 }
 
 impl Path {
     pub fn new(reversed_segments: Vec<Box<dyn PathSegment>>) -> Self {
+        Self::from_initial(reversed_segments.into_iter().rev().collect())
+    }
+    fn from_initial(initial: Rc<[Box<dyn PathSegment>]>) -> Self {
         Self {
-            segments: reversed_segments.into_iter().rev().collect(),
+            segments: initial.iter().map(|v| v.as_ref().boxed_clone()).collect(),
             current_segment: None,
+            initial,
+            trace_id: None,
+            outcome: Outcome::Succeeded,
+            paused_at: None,
         }
     }
+    // copies `initial` into a new list with `v` added, `initial` can't be
+    // changed in place as it may be shared with clones
+    fn add_initial(&mut self, v: &dyn PathSegment, front: bool) {
+        let copies = self.initial.iter().map(|v| v.as_ref().boxed_clone());
+        let v = std::iter::once(v.boxed_clone());
+        self.initial = if front {
+            copies.chain(v).collect()
+        } else {
+            v.chain(copies).collect()
+        };
+    }
     pub fn extend(&mut self, v: Box<dyn PathSegment>) {
+        self.add_initial(v.as_ref(), false);
         self.segments.push_front(v);
     }
     pub fn extend_front(&mut self, v: Box<dyn PathSegment>) {
+        self.add_initial(v.as_ref(), true);
         self.segments.push_back(v);
     }
+    /// Puts the path back to how it was before it was first followed so it
+    /// can be run again.
+    pub fn reset(&mut self) {
+        *self = self.clone();
+    }
 }
 
 #[macro_export]
@@ -44,12 +122,25 @@ macro_rules! path {
         }
 }
 
+impl std::fmt::Debug for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Path")
+            .field("segments", &self.segments)
+            .field("current_segment", &self.current_segment)
+            .finish_non_exhaustive()
+    }
+}
+
+// clones start from the beginning, even if this path is part way through
+impl Clone for Path {
+    fn clone(&self) -> Self {
+        Self::from_initial(self.initial.clone())
+    }
+}
+
 impl From<Box<dyn PathSegment>> for Path {
     fn from(seg: Box<dyn PathSegment>) -> Self {
-        Self {
-            segments: vec![seg].into(),
-            current_segment: None,
-        }
+        Self::new(vec![seg])
     }
 }
 
@@ -62,6 +153,7 @@ impl Path {
         while let Some(mut new_seg) = self.segments.pop_back() {
             if new_seg.finished_transform() {
                 log::info!("started new segment: {new_seg:?}");
+                self.trace_id = trace::segment_started(new_seg.as_ref(), odom);
                 new_seg.start(odom, angle_pid, pkt);
                 self.current_segment = Some(new_seg);
                 return;
@@ -75,7 +167,7 @@ impl Path {
 
         // exit when no segments could be transformed
         let Some(seg) = self.current_segment.as_mut() else {
-            return PathOutput::Voltages(Vec2::ZERO);
+            return PathOutput::Idle;
         };
 
         // end segment and start next
@@ -85,21 +177,86 @@ impl Path {
             } else {
                 log::info!("segment_ended: {seg:?}");
             }
-            self.segments.extend(new_segments);
+            let reason = seg.end_reason();
+            let outcome = seg.outcome();
             self.current_segment = None;
+            self.end_trace(odom, reason, &outcome);
+            if outcome.is_failure() {
+                // stop here and let the parent decide what to do
+                log::warn!("segment {outcome}, skipping the rest of the path");
+                self.segments.clear();
+                self.outcome = outcome;
+                return PathOutput::Idle;
+            }
+            if !outcome.is_success() {
+                log::warn!("segment {outcome}, carrying on with the path");
+                if self.outcome.is_success() {
+                    self.outcome = outcome;
+                }
+            }
+            self.segments.extend(new_segments);
             return self.follow(odom, angle_pid, pkt);
         }
 
-        seg.follow(odom, angle_pid, pkt)
+        let out = seg.follow(odom, angle_pid, pkt);
+        if let PathOutput::SwitchToDriver = out {
+            self.end_trace(odom, EndReason::SwitchToDriver, &Outcome::Succeeded);
+        }
+        out
     }
     fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
         if let Some(seg) = self.current_segment.as_mut() {
             seg.abrupt_end(odom, pkt);
+            self.end_trace(odom, EndReason::Aborted, &Outcome::Succeeded);
+        }
+    }
+    fn end_trace(&mut self, odom: &Odom, reason: EndReason, outcome: &Outcome) {
+        if let Some(id) = self.trace_id.take() {
+            let failure = match outcome {
+                Outcome::Failed(reason) => Some(reason.clone()),
+                _ => None,
+            };
+            trace::segment_ended(id, odom, reason, failure);
         }
     }
     pub fn ended(&self) -> bool {
         self.current_segment.is_none() && self.segments.is_empty()
     }
+    /// Holds the path where it is, the caller should stop following it until
+    /// `resume` is called.
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            log::info!("pausing path");
+            self.paused_at = Some(clock::now());
+        }
+    }
+    /// Carries on from where `pause` was called. Timers in the running
+    /// segments and `angle_pid` are moved forward so the time spent paused
+    /// isn't counted.
+    pub fn resume(&mut self, angle_pid: &mut Pid) {
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused = clock::elapsed(paused_at);
+            log::info!("resuming path after {paused:?}");
+            PathSegment::shift_time(self, paused);
+            angle_pid.shift_time(paused);
+        }
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+    /// Stops the path for good, calling `abrupt_end` down through every
+    /// running segment so mechanisms are released. The drivetrain is left to
+    /// the caller.
+    pub fn cancel(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        if self.ended() {
+            return;
+        }
+        log::info!("cancelling path");
+        self.abrupt_end(odom, pkt);
+        self.current_segment = None;
+        self.segments.clear();
+        self.outcome = Outcome::Failed("cancelled".to_owned());
+    }
 }
 
 pub trait PathSegment: std::fmt::Debug {
@@ -119,9 +276,36 @@ pub trait PathSegment: std::fmt::Debug {
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>>;
     fn abrupt_end(&mut self, _odom: &Odom, pkt: &mut ToBrain) {}
-    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
-        panic!("This type is designed to not be clonable: {self:?}");
+    /// Whether the segment did its job, asked once `end_follow` has returned
+    /// `Some`. A `Path` stops at the first segment that fails.
+    fn outcome(&self) -> Outcome {
+        Outcome::Succeeded
+    }
+    /// Why the segment ended, asked once `end_follow` has returned `Some`.
+    fn end_reason(&self) -> EndReason {
+        match self.outcome() {
+            Outcome::Succeeded => EndReason::Completed,
+            Outcome::TimedOut => EndReason::TimedOut,
+            Outcome::Failed(_) => EndReason::Failed,
+        }
+    }
+    /// Moves the segment's timers forward by `by`, called on the running
+    /// segments when a paused path resumes.
+    fn shift_time(&mut self, _by: Duration) {}
+    /// Type name used in traces.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
     }
+    /// Label given with `Labeled`, used in traces.
+    fn label(&self) -> Option<&str> {
+        None
+    }
+    /// Returns a copy in the state the segment was created in, so the copy
+    /// runs the same way the original did even if the original has already
+    /// started.
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a>;
 }
 
 impl PathSegment for Path {
@@ -146,22 +330,20 @@ impl PathSegment for Path {
     fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
         Path::abrupt_end(self, odom, pkt);
     }
+    fn outcome(&self) -> Outcome {
+        self.outcome.clone()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        if let Some(seg) = self.current_segment.as_mut() {
+            seg.shift_time(by);
+        }
+    }
     fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
-        Box::new(Self {
-            segments: self
-                .segments
-                .iter()
-                .map(|v| v.as_ref().boxed_clone())
-                .collect(),
-            current_segment: self
-                .current_segment
-                .as_ref()
-                .map(|v| v.as_ref().boxed_clone()),
-        })
+        Box::new(self.clone())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RamsetePoint {
     target: (Vec2, f64),
     controller: Ramsete,
@@ -197,37 +379,78 @@ impl PathSegment for RamsetePoint {
         }
         None
     }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(self.clone())
+    }
+}
+
+/// Where along a `RamsetePath` a marker fires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerTrigger {
+    /// once the waypoint at this index has been reached
+    Waypoint(usize),
+    /// once the robot has driven this far (mm) since the path started
+    Distance(f64),
+    /// once the robot is within this distance (mm) of the end of the path,
+    /// measured along the waypoints
+    BeforeEnd(f64),
+}
+
+#[derive(Debug)]
+struct Marker {
+    trigger: MarkerTrigger,
+    path: Path,
+    fired: bool,
 }
 
 #[derive(Debug)]
 pub struct RamsetePath {
+    waypoints: VecDeque<(Vec2, f64)>,
     target: VecDeque<(Vec2, f64)>,
     current_target: Option<(Vec2, f64)>,
     controller: Ramsete,
+    markers: Vec<Marker>,
+    // marker paths that have fired and are still going
+    running: Vec<Path>,
+    reached: usize,
+    travelled: f64,
+    length: f64,
+    last_pos: Vec2,
 }
 
 impl RamsetePath {
     pub fn new<T: Into<VecDeque<(Vec2, f64)>>>(target: T, mut controller: Ramsete) -> Self {
-        let mut target: VecDeque<_> = target.into();
+        let waypoints: VecDeque<_> = target.into();
+        let mut target = waypoints.clone();
         let current_target = target.pop_front();
         if let Some(v) = current_target {
             controller.set_target(v);
         }
         Self {
+            waypoints,
             target,
             current_target,
             controller,
+            markers: Vec::new(),
+            running: Vec::new(),
+            reached: 0,
+            travelled: 0.0,
+            length: 0.0,
+            last_pos: Vec2::ZERO,
         }
     }
-}
-
-impl PathSegment for RamsetePath {
-    fn finished_transform(&self) -> bool {
-        true
+    /// Runs `path` alongside the drive once `trigger` is hit. Drivetrain
+    /// output from marker paths is ignored and any still running when the
+    /// drive finishes are ended abruptly.
+    pub fn marker(mut self, trigger: MarkerTrigger, path: Path) -> Self {
+        self.markers.push(Marker {
+            trigger,
+            path,
+            fired: false,
+        });
+        self
     }
-
-    fn start(&mut self, _: &Odom, _: &mut Pid, pkt: &mut ToBrain) {}
-    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
+    fn drive(&mut self, odom: &Odom) -> PathOutput {
         let Some(target) = self.current_target else {
             return PathOutput::Voltages(Vec2::ZERO);
         };
@@ -239,41 +462,411 @@ impl PathSegment for RamsetePath {
             && (odom.heading() - target.1).abs() < 30f64.to_radians()
             || diff.dot(nor) > 0.0
         {
+            self.reached += 1;
             self.current_target = self.target.pop_front();
             if let Some(target) = self.current_target {
                 log::error!("new ramsete target: {target:?}");
                 self.controller.set_target(target);
-                return self.follow(odom, angle_pid, pkt);
+                return self.drive(odom);
             }
         }
 
         PathOutput::LinearAngularVelocity(self.controller.output_linear_angular(odom))
     }
+    fn run_markers(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) {
+        self.travelled += (odom.pos() - self.last_pos).mag();
+        self.last_pos = odom.pos();
+        let remaining = self.length - self.travelled;
+        for marker in self.markers.iter_mut().filter(|v| !v.fired) {
+            marker.fired = match marker.trigger {
+                MarkerTrigger::Waypoint(idx) => self.reached > idx,
+                MarkerTrigger::Distance(dist) => self.travelled >= dist,
+                MarkerTrigger::BeforeEnd(dist) => remaining <= dist,
+            };
+            if marker.fired {
+                log::info!("ramsete marker {:?} fired", marker.trigger);
+                self.running.push(marker.path.clone());
+            }
+        }
+        for path in &mut self.running {
+            let _ = path.follow(odom, angle_pid, pkt);
+        }
+        self.running.retain(|v| !v.ended());
+    }
+}
+
+impl PathSegment for RamsetePath {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+
+    fn start(&mut self, odom: &Odom, _: &mut Pid, pkt: &mut ToBrain) {
+        // length from where the robot is, through every waypoint
+        let mut last = odom.pos();
+        self.length = 0.0;
+        for (pos, _) in &self.waypoints {
+            self.length += (*pos - last).mag();
+            last = *pos;
+        }
+        self.last_pos = odom.pos();
+        self.travelled = 0.0;
+    }
+    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
+        let out = self.drive(odom);
+        self.run_markers(odom, angle_pid, pkt);
+        out
+    }
 
     fn end_follow<'a>(
         &mut self,
-        _: &Odom,
+        odom: &Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
         if self.current_target.is_none() {
+            self.abrupt_end(odom, pkt);
+            return Some(Vec::new());
+        }
+        None
+    }
+    fn abrupt_end(&mut self, odom: &Odom, pkt: &mut ToBrain) {
+        for mut path in self.running.drain(..) {
+            path.abrupt_end(odom, pkt);
+        }
+    }
+    fn shift_time(&mut self, by: Duration) {
+        for path in &mut self.running {
+            PathSegment::shift_time(path, by);
+        }
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        let mut path = Self::new(self.waypoints.clone(), self.controller.clone());
+        for marker in &self.markers {
+            path = path.marker(marker.trigger, marker.path.clone());
+        }
+        Box::new(path)
+    }
+}
+
+/// Follows waypoints by steering towards the point `lookahead` mm further
+/// along the path, driving the wheels at speeds for the arc that reaches
+/// it. The lookahead point only moves forward, to the first place the
+/// lookahead circle crosses the path, so dense paths and paths that come
+/// back near themselves don't make it skip ahead. The path ends near the
+/// last waypoint, once the lookahead point is on the last segment.
+#[derive(Debug, Clone)]
+pub struct PurePursuitPath {
+    waypoints: Vec<Vec2>,
+    radius: f64,
+    lookahead: f64,
+    max_velocity: f64,
+    tolerance: f64,
+    // how far along the path the lookahead point is, as segment index plus
+    // the fraction through that segment
+    progress: f64,
+}
+
+impl PurePursuitPath {
+    // never slow below this (mm/s) while approaching the end
+    const MIN_VELOCITY: f64 = 100.0;
+    pub fn new<T: IntoIterator<Item = Vec2>, const N: usize>(
+        waypoints: T,
+        drivebase: &Drivebase<N>,
+    ) -> Self {
+        Self {
+            waypoints: waypoints.into_iter().collect(),
+            radius: drivebase.radius(),
+            lookahead: 300.0,
+            max_velocity: 800.0,
+            tolerance: 30.0,
+            progress: 0.0,
+        }
+    }
+    /// 300mm by default, longer is smoother but cuts corners more.
+    pub fn lookahead(mut self, lookahead: f64) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+    /// Top wheel speed in mm/s, 800 by default.
+    pub fn max_velocity(mut self, max_velocity: f64) -> Self {
+        self.max_velocity = max_velocity;
+        self
+    }
+    /// How close (mm) to the last waypoint counts as done, 30 by default.
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    fn end(&self) -> Option<Vec2> {
+        self.waypoints.last().copied()
+    }
+    // the end checks only apply from here, otherwise loops and paths that
+    // come back past their end would stop early
+    fn on_last_segment(&self) -> bool {
+        self.progress as usize + 2 >= self.waypoints.len()
+    }
+    // first point that is `lookahead` from `pos` and not behind the current
+    // one, looking at most `lookahead` further along the path
+    fn lookahead_point(&mut self, pos: Vec2) -> Vec2 {
+        let start_idx = self.progress as usize;
+        // path length from the current point to the start of segment idx
+        let mut along = 0.0;
+        for idx in start_idx..self.waypoints.len().saturating_sub(1) {
+            if along > self.lookahead {
+                break;
+            }
+            let (a, b) = (self.waypoints[idx], self.waypoints[idx + 1]);
+            let d = b - a;
+            along += if idx == start_idx {
+                d.mag() * (1.0 - self.progress.fract())
+            } else {
+                d.mag()
+            };
+            let f = a - pos;
+            let qa = d.dot(d);
+            let qb = 2.0 * f.dot(d);
+            let qc = f.dot(f) - self.lookahead.powi(2);
+            let disc = qb * qb - 4.0 * qa * qc;
+            if qa == 0.0 || disc < 0.0 {
+                continue;
+            }
+            // the larger root is the intersection further along
+            let t = (-qb + disc.sqrt()) / (2.0 * qa);
+            if (0.0..=1.0).contains(&t) && idx as f64 + t >= self.progress {
+                self.progress = idx as f64 + t;
+                break;
+            }
+        }
+        let idx = self.progress as usize;
+        match (self.waypoints.get(idx), self.waypoints.get(idx + 1)) {
+            (Some(&a), Some(&b)) => a + (b - a) * self.progress.fract(),
+            (Some(&a), None) => a,
+            _ => pos,
+        }
+    }
+}
+
+impl PathSegment for PurePursuitPath {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) {
+        self.progress = 0.0;
+    }
+    fn follow(&mut self, odom: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        let Some(end) = self.end() else {
+            return PathOutput::Voltages(Vec2::ZERO);
+        };
+        let pos = odom.pos();
+        let to_end = (end - pos).mag();
+        let mut target = self.lookahead_point(pos);
+        let near_end = self.on_last_segment() && to_end < self.lookahead;
+        if near_end {
+            target = end;
+        }
+
+        // curvature of the arc through the target that the robot is
+        // tangent to, positive turns left
+        let to_target = target - pos;
+        let (sin, cos) = odom.heading().sin_cos();
+        let lateral = -sin * to_target.x + cos * to_target.y;
+        let curvature = 2.0 * lateral / to_target.mag_sq().max(1.0);
+
+        let velocity = if near_end {
+            (self.max_velocity * to_end / self.lookahead).max(Self::MIN_VELOCITY)
+        } else {
+            self.max_velocity
+        };
+        let left = velocity * (1.0 - curvature * self.radius);
+        let right = velocity * (1.0 + curvature * self.radius);
+        // keep the turn the same if a side would go over the max
+        let scale = (self.max_velocity / left.abs().max(right.abs())).min(1.0);
+        PathOutput::WheelVelocities(Vec2::new(left, right) * scale)
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        let Some(end) = self.end() else {
+            return Some(Vec::new());
+        };
+        if !self.on_last_segment() {
+            return None;
+        }
+        let to_end = end - odom.pos();
+        // also end once the robot has gone past the end
+        let passed = match self.waypoints.len() {
+            n if n >= 2 => to_end.dot(end - self.waypoints[n - 2]) < 0.0,
+            _ => false,
+        };
+        if to_end.mag() < self.tolerance || passed {
             return Some(Vec::new());
         }
         None
     }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self {
+            progress: 0.0,
+            ..self.clone()
+        })
+    }
+}
+
+/// Follows a `Trajectory` in time, using `TrajectoryGains` to correct for
+/// where the robot actually is. Ends once the trajectory's time is up.
+#[derive(Debug, Clone)]
+pub struct TrajectoryPath {
+    trajectory: Trajectory,
+    gains: TrajectoryGains,
+    radius: f64,
+    start_time: Instant,
+}
+
+impl TrajectoryPath {
+    pub fn new<const N: usize>(
+        trajectory: Trajectory,
+        gains: TrajectoryGains,
+        drivebase: &Drivebase<N>,
+    ) -> Self {
+        Self {
+            trajectory,
+            gains,
+            radius: drivebase.radius(),
+            start_time: clock::now(),
+        }
+    }
+    fn elapsed(&self) -> f64 {
+        clock::elapsed(self.start_time).as_secs_f64()
+    }
+}
+
+impl PathSegment for TrajectoryPath {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) {
+        self.start_time = clock::now();
+    }
+    fn follow(&mut self, odom: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        let Some(state) = self.trajectory.sample(self.elapsed()) else {
+            return PathOutput::Voltages(Vec2::ZERO);
+        };
+        let Vec2 {
+            x: linear,
+            y: angular,
+        } = self.gains.output(odom, &state);
+        PathOutput::WheelVelocities(Vec2::new(
+            linear - angular * self.radius,
+            linear + angular * self.radius,
+        ))
+    }
+    fn end_follow<'a>(
+        &mut self,
+        _: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        if self.elapsed() >= self.trajectory.duration() {
+            return Some(Vec::new());
+        }
+        None
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.start_time += by;
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self {
+            trajectory: self.trajectory.clone(),
+            gains: self.gains,
+            radius: self.radius,
+            start_time: clock::now(),
+        })
+    }
+}
+
+/// Ending rules shared by the segments that drive to a target: done once
+/// on target for `SETTLE_TIME`, or timed out once the optional timeout has
+/// passed since the segment started.
+#[derive(Debug, Clone, Copy)]
+struct Settle {
+    timeout: Option<Duration>,
+    start: Instant,
+    settled_since: Option<Instant>,
+    timed_out: bool,
+}
+
+impl Settle {
+    const SETTLE_TIME: Duration = Duration::from_millis(200);
+    fn new() -> Self {
+        Self {
+            timeout: None,
+            start: clock::now(),
+            settled_since: None,
+            timed_out: false,
+        }
+    }
+    // a copy that hasn't started, keeping the timeout
+    fn fresh(&self) -> Self {
+        Self {
+            timeout: self.timeout,
+            ..Self::new()
+        }
+    }
+    fn start(&mut self) {
+        *self = self.fresh();
+    }
+    /// Called from `end_follow` with whether the segment is on target this
+    /// tick, returns how it ended once it has.
+    fn update(&mut self, on_target: bool) -> Option<Outcome> {
+        if self
+            .timeout
+            .is_some_and(|timeout| clock::elapsed(self.start) > timeout)
+        {
+            self.timed_out = true;
+            return Some(Outcome::TimedOut);
+        }
+        if !on_target {
+            self.settled_since = None;
+            return None;
+        }
+        let settled_since = *self.settled_since.get_or_insert_with(clock::now);
+        (clock::elapsed(settled_since) > Self::SETTLE_TIME).then_some(Outcome::Succeeded)
+    }
+    fn outcome(&self) -> Outcome {
+        if self.timed_out {
+            Outcome::TimedOut
+        } else {
+            Outcome::Succeeded
+        }
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.start += by;
+        if let Some(settled_since) = self.settled_since.as_mut() {
+            *settled_since += by;
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct TurnTo {
+    // the heading as given, target_heading is moved to the closest
+    // equivalent angle on start
+    target: f64,
     target_heading: f64,
-    end_time: Option<Instant>,
+    settle: Settle,
 }
 impl TurnTo {
     pub fn new(target_heading: f64) -> Self {
         Self {
+            target: target_heading,
             target_heading,
-            end_time: None,
+            settle: Settle::new(),
         }
     }
+    /// Gives up with `Outcome::TimedOut` if the turn hasn't settled in time.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.settle.timeout = Some(timeout);
+        self
+    }
 }
 
 impl PathSegment for TurnTo {
@@ -284,12 +877,10 @@ impl PathSegment for TurnTo {
         self.target_heading = optimise_target_heading(odom.heading(), self.target_heading);
         angle_pid.set_target(self.target_heading);
         angle_pid.reset();
+        self.settle.start();
     }
     fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
         let pow = angle_pid.poll(odom.heading());
-        if (odom.heading() - self.target_heading).abs() > 2f64.to_radians() {
-            self.end_time = Some(Instant::now());
-        }
         PathOutput::Voltages(Vec2::new(-pow, pow))
     }
     fn end_follow<'a>(
@@ -297,22 +888,103 @@ impl PathSegment for TurnTo {
         odom: &Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
-        match self.end_time {
-            Some(end_time) => {
-                if end_time.elapsed() > Duration::from_millis(200) {
-                    log::info!(
-                        "Finished segment - TurnTo({}) with heading ({}).",
-                        self.target_heading,
-                        odom.heading()
-                    );
-                    return Some(vec![]);
-                }
+        let on_target = (odom.heading() - self.target_heading).abs() <= 2f64.to_radians();
+        match self.settle.update(on_target)? {
+            Outcome::TimedOut => log::warn!(
+                "TurnTo({}) timed out at heading ({}).",
+                self.target_heading,
+                odom.heading()
+            ),
+            _ => log::info!(
+                "Finished segment - TurnTo({}) with heading ({}).",
+                self.target_heading,
+                odom.heading()
+            ),
+        }
+        Some(vec![])
+    }
+    fn outcome(&self) -> Outcome {
+        self.settle.outcome()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.settle.shift_time(by);
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self {
+            settle: self.settle.fresh(),
+            ..Self::new(self.target)
+        })
+    }
+}
 
-                None
-            }
+/// Turns to face a point on the field, the heading is worked out from where
+/// the robot is once the segment is reached and then turned to as `TurnTo`.
+/// If the robot is already within `TurnToPoint::MIN_DISTANCE` of the point
+/// the heading would be noise, so it finishes without turning.
+#[derive(Debug, Clone)]
+pub struct TurnToPoint {
+    point: Vec2,
+    backward: bool,
+    timeout: Option<Duration>,
+}
+
+impl TurnToPoint {
+    /// mm from the point below which the robot doesn't turn
+    pub const MIN_DISTANCE: f64 = 10.0;
+
+    pub fn new(point: Vec2) -> Self {
+        Self {
+            point,
+            backward: false,
+            timeout: None,
+        }
+    }
+    /// Points the back of the robot at the point instead, for mechanisms
+    /// on the back like the back latch.
+    pub fn backward(mut self) -> Self {
+        self.backward = true;
+        self
+    }
+    /// See `TurnTo::timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
 
-            None => None,
+impl PathSegment for TurnToPoint {
+    fn transform<'a>(self: Box<Self>, odom: &Odom) -> Vec<Box<dyn PathSegment + 'a>> {
+        let offset = self.point - odom.pos();
+        if offset.mag() < Self::MIN_DISTANCE {
+            log::info!("already at {:?}, not turning", self.point);
+            return Vec::new();
         }
+        let mut heading = offset.angle();
+        if self.backward {
+            heading += PI;
+        }
+        let mut turn = TurnTo::new(heading);
+        turn.settle.timeout = self.timeout;
+        vec![Box::new(turn)]
+    }
+    fn finished_transform(&self) -> bool {
+        false
+    }
+    fn start(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) {
+        unreachable!("TurnToPoint is always transformed before it starts");
+    }
+    fn follow(&mut self, _: &Odom, _: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        unreachable!("TurnToPoint is always transformed before it starts");
+    }
+    fn end_follow<'a>(
+        &mut self,
+        _: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        unreachable!("TurnToPoint is always transformed before it starts");
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(self.clone())
     }
 }
 
@@ -324,7 +996,7 @@ pub struct PowerSide {
 }
 impl PowerSide {
 pub fn new(turn_angle: f64, neg: bool) -> Self {
-    Self { mul: turn_angle / 180.0, neg, start: Instant::now() }
+    Self { mul: turn_angle / 180.0, neg, start: clock::now() }
 }
 }
 impl PathSegment for PowerSide {
@@ -332,7 +1004,7 @@ impl PathSegment for PowerSide {
         true
     }
     fn start(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) {
-        self.start = Instant::now();
+        self.start = clock::now();
     }
     fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
         if self.neg {
@@ -347,11 +1019,17 @@ impl PathSegment for PowerSide {
         odom: &Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
-        if self.start.elapsed() > Duration::from_secs_f64(2.815 * self.mul) {
+        if clock::elapsed(self.start) > Duration::from_secs_f64(2.815 * self.mul) {
             return Some(Vec::new());
         }
         None
     }
+    fn shift_time(&mut self, by: Duration) {
+        self.start += by;
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(self.clone())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -359,16 +1037,28 @@ pub struct Ram {
     pow: f64,
     dur: std::time::Duration,
     start: std::time::Instant,
+    expect_contact: bool,
+    outcome: Outcome,
 }
 
 impl Ram {
+    // below this speed (mm/s) the robot is taken to be pushing on something
+    const CONTACT_SPEED: f64 = 50.0;
     pub fn new(pow: f64, dur: std::time::Duration) -> Self {
         Self {
             pow,
             dur,
-            start: std::time::Instant::now(),
+            start: clock::now(),
+            expect_contact: false,
+            outcome: Outcome::Succeeded,
         }
     }
+    /// Fails if the robot is still moving freely when the ram ends, meaning
+    /// it didn't hit what it was aimed at.
+    pub fn expect_contact(mut self) -> Self {
+        self.expect_contact = true;
+        self
+    }
 }
 
 impl PathSegment for Ram {
@@ -376,33 +1066,327 @@ impl PathSegment for Ram {
         true
     }
     fn start(&mut self, _: &Odom, _: &mut Pid, pkt: &mut ToBrain) {
-        self.start = std::time::Instant::now();
+        self.start = clock::now();
+        self.outcome = Outcome::Succeeded;
     }
     fn follow(&mut self, _: &Odom, _: &mut Pid, pkt: &mut ToBrain) -> PathOutput {
         PathOutput::Voltages(Vec2::splat(self.pow))
     }
     fn end_follow<'a>(
         &mut self,
-        _: &Odom,
+        odom: &Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
-        if self.start.elapsed() > self.dur {
+        if clock::elapsed(self.start) > self.dur {
+            if self.expect_contact && odom.velocity() > Self::CONTACT_SPEED {
+                self.outcome = Outcome::Failed(format!(
+                    "ram made no contact, still moving at {:.0}mm/s",
+                    odom.velocity()
+                ));
+            }
             return Some(Vec::new());
         }
         None
     }
+    fn outcome(&self) -> Outcome {
+        self.outcome.clone()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.start += by;
+    }
     fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
-        Box::new(self.clone())
+        Box::new(Self {
+            start: clock::now(),
+            outcome: Outcome::Succeeded,
+            ..self.clone()
+        })
+    }
+}
+/// Drives straight for `distance` mm (negative for backwards) using the
+/// odometry, holding the heading it started at with the shared angle pid.
+/// Ends once within `tolerance` of the target and nearly stopped for the
+/// settle time.
+#[derive(Debug)]
+pub struct DriveDistance {
+    distance: f64,
+    linear_pid: Pid,
+    max_power: f64,
+    tolerance: f64,
+    start_pos: Vec2,
+    start_heading: f64,
+    settle: Settle,
+}
+
+impl DriveDistance {
+    // mm/s
+    const SETTLE_SPEED: f64 = 30.0;
+    pub fn new(distance: f64, linear_pid: Pid) -> Self {
+        Self {
+            distance,
+            linear_pid,
+            max_power: 0.8,
+            tolerance: 10.0,
+            start_pos: Vec2::ZERO,
+            start_heading: 0.0,
+            settle: Settle::new(),
+        }
+    }
+    /// Caps the forward power, 0.8 by default.
+    pub fn max_power(mut self, max_power: f64) -> Self {
+        self.max_power = max_power;
+        self
+    }
+    /// How close (mm) counts as there, 10mm by default.
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    /// See `TurnTo::timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.settle.timeout = Some(timeout);
+        self
+    }
+    // distance covered along the starting heading
+    fn travelled(&self, odom: &Odom) -> f64 {
+        let (sin, cos) = self.start_heading.sin_cos();
+        (odom.pos() - self.start_pos).dot(Vec2::new(cos, sin))
+    }
+}
+
+impl PathSegment for DriveDistance {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, odom: &Odom, angle_pid: &mut Pid, _: &mut ToBrain) {
+        self.start_pos = odom.pos();
+        self.start_heading = odom.heading();
+        self.settle.start();
+        self.linear_pid.set_target(self.distance);
+        self.linear_pid.reset();
+        angle_pid.set_target(self.start_heading);
+        angle_pid.reset();
+    }
+    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        let forward = self
+            .linear_pid
+            .poll(self.travelled(odom))
+            .clamp(-self.max_power, self.max_power);
+        let turn = angle_pid.poll(odom.heading());
+        PathOutput::Voltages(Vec2::new(forward - turn, forward + turn))
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        let on_target = (self.distance - self.travelled(odom)).abs() < self.tolerance
+            && odom.velocity() < Self::SETTLE_SPEED;
+        match self.settle.update(on_target)? {
+            Outcome::TimedOut => log::warn!(
+                "DriveDistance({}) timed out after {}mm",
+                self.distance,
+                self.travelled(odom)
+            ),
+            _ => log::info!(
+                "Finished segment - DriveDistance({}) travelled ({}).",
+                self.distance,
+                self.travelled(odom)
+            ),
+        }
+        Some(Vec::new())
+    }
+    fn outcome(&self) -> Outcome {
+        self.settle.outcome()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.settle.shift_time(by);
+        self.linear_pid.shift_time(by);
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self {
+            max_power: self.max_power,
+            tolerance: self.tolerance,
+            settle: self.settle.fresh(),
+            ..Self::new(self.distance, self.linear_pid.clone())
+        })
     }
 }
+
+/// Drives to a position and heading with a boomerang controller: the robot
+/// heads for a carrot point behind the target along the final heading,
+/// which pulls it onto the right heading as it arrives. `lead` (0 to 1)
+/// sets how far back the carrot is as a fraction of the distance left.
+///
+/// The shared angle pid steers and `linear_pid` (power per mm) drives, the
+/// output is voltages so velocity control doesn't need to be tuned.
+#[derive(Debug)]
+pub struct MoveToPose {
+    target: (Vec2, f64),
+    linear_pid: Pid,
+    lead: f64,
+    backward: bool,
+    max_power: f64,
+    tolerance: f64,
+    heading_tolerance: f64,
+    settle: Settle,
+}
+
+impl MoveToPose {
+    // within this distance (mm) stop chasing the carrot and just line up
+    // with the final heading, otherwise the robot spins about the target
+    const NEAR: f64 = 75.0;
+    pub fn new(target: (Vec2, f64), linear_pid: Pid) -> Self {
+        Self {
+            target,
+            linear_pid,
+            lead: 0.6,
+            backward: false,
+            max_power: 0.8,
+            tolerance: 20.0,
+            heading_tolerance: 3f64.to_radians(),
+            settle: Settle::new(),
+        }
+    }
+    /// 0.6 by default, 0 drives straight at the target.
+    pub fn lead(mut self, lead: f64) -> Self {
+        self.lead = lead;
+        self
+    }
+    /// Drives there backwards, `target.1` is still the heading of the front.
+    pub fn backward(mut self) -> Self {
+        self.backward = true;
+        self
+    }
+    /// See `DriveDistance::max_power`.
+    pub fn max_power(mut self, max_power: f64) -> Self {
+        self.max_power = max_power;
+        self
+    }
+    /// How close in mm and radians counts as there, 20mm and 3° by default.
+    pub fn tolerance(mut self, tolerance: f64, heading_tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self.heading_tolerance = heading_tolerance;
+        self
+    }
+    /// See `TurnTo::timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.settle.timeout = Some(timeout);
+        self
+    }
+    // direction the robot drives in when facing `heading`
+    fn travel_dir(&self, heading: f64) -> Vec2 {
+        let (sin, cos) = heading.sin_cos();
+        if self.backward {
+            Vec2::new(-cos, -sin)
+        } else {
+            Vec2::new(cos, sin)
+        }
+    }
+    fn heading_error(&self, odom: &Odom) -> f64 {
+        optimise_target_heading(odom.heading(), self.target.1) - odom.heading()
+    }
+}
+
+impl PathSegment for MoveToPose {
+    fn finished_transform(&self) -> bool {
+        true
+    }
+    fn start(&mut self, _: &Odom, angle_pid: &mut Pid, _: &mut ToBrain) {
+        self.settle.start();
+        // the pid works on the distance left directly
+        self.linear_pid.set_target(0.0);
+        self.linear_pid.reset();
+        angle_pid.reset();
+    }
+    fn follow(&mut self, odom: &Odom, angle_pid: &mut Pid, _: &mut ToBrain) -> PathOutput {
+        let (target, target_heading) = self.target;
+        let to_target = target - odom.pos();
+        let dist = to_target.mag();
+
+        let (heading, dist_err) = if dist < Self::NEAR {
+            // only the part of the error along the final heading can be
+            // driven out from here
+            let along = to_target.dot(self.travel_dir(target_heading));
+            (target_heading, along)
+        } else {
+            let carrot = target - self.travel_dir(target_heading) * (self.lead * dist);
+            let to_carrot = carrot - odom.pos();
+            let mut heading = to_carrot.angle();
+            if self.backward {
+                heading += PI;
+            }
+            // slow down while not facing the carrot
+            let facing = to_carrot.dot(self.travel_dir(odom.heading()));
+            (heading, facing)
+        };
+
+        angle_pid.set_target(optimise_target_heading(odom.heading(), heading));
+        let turn = angle_pid.poll(odom.heading());
+
+        let mut forward = self
+            .linear_pid
+            .poll(-dist_err)
+            .clamp(-self.max_power, self.max_power);
+        if self.backward {
+            forward = -forward;
+        }
+        // turning takes priority when the sides would saturate
+        let headroom = (1.0 - turn.abs()).max(0.0);
+        forward = forward.clamp(-headroom, headroom);
+
+        PathOutput::Voltages(Vec2::new(forward - turn, forward + turn))
+    }
+    fn end_follow<'a>(
+        &mut self,
+        odom: &Odom,
+        _: &mut ToBrain,
+    ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
+        let on_target = (self.target.0 - odom.pos()).mag() < self.tolerance
+            && self.heading_error(odom).abs() < self.heading_tolerance;
+        match self.settle.update(on_target)? {
+            Outcome::TimedOut => log::warn!(
+                "MoveToPose({:?}) timed out at ({:?}, {}).",
+                self.target,
+                odom.pos(),
+                odom.heading()
+            ),
+            _ => log::info!(
+                "Finished segment - MoveToPose({:?}) at ({:?}, {}).",
+                self.target,
+                odom.pos(),
+                odom.heading()
+            ),
+        }
+        Some(Vec::new())
+    }
+    fn outcome(&self) -> Outcome {
+        self.settle.outcome()
+    }
+    fn shift_time(&mut self, by: Duration) {
+        self.settle.shift_time(by);
+        self.linear_pid.shift_time(by);
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self {
+            lead: self.lead,
+            backward: self.backward,
+            max_power: self.max_power,
+            tolerance: self.tolerance,
+            heading_tolerance: self.heading_tolerance,
+            settle: self.settle.fresh(),
+            ..Self::new(self.target, self.linear_pid.clone())
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PowerMotors {
-    motors: Vec<usize>,
+    motors: Vec<SmartPortHandle>,
     pow: MotorControl,
     shaking: Vec<usize>,
 }
 impl PowerMotors {
-    pub fn new(motors: Vec<usize>, pow: MotorControl) -> Self {
+    pub fn new(motors: Vec<SmartPortHandle>, pow: MotorControl) -> Self {
         Self {
             motors,
             pow,
@@ -424,14 +1408,14 @@ impl PathSegment for PowerMotors {
         _: &mut crate::pid::Pid,
         pkt: &mut ToBrain,
     ) -> crate::path::PathOutput {
-        for motor_idx in &self.motors {
-            pkt.set_motors[motor_idx - 1] = self.pow;
+        for motor in &self.motors {
+            motor.set_motor(pkt, self.pow);
         }
-        crate::path::PathOutput::Voltages(crate::vec::Vec2::ZERO)
+        crate::path::PathOutput::Idle
     }
     fn abrupt_end(&mut self, _odom: &Odom, pkt: &mut ToBrain) {
-        for motor_idx in &self.motors {
-            pkt.set_motors[motor_idx - 1] = MotorControl::BrakeCoast;
+        for motor in &self.motors {
+            motor.set_motor(pkt, MotorControl::BrakeCoast);
         }
     }
     fn end_follow<'a>(
@@ -439,22 +1423,37 @@ impl PathSegment for PowerMotors {
         _: &crate::odometry::Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
-        for motor_idx in &self.motors {
-            pkt.set_motors[motor_idx - 1] = MotorControl::BrakeCoast;
+        for motor in &self.motors {
+            motor.set_motor(pkt, MotorControl::BrakeCoast);
         }
         None
     }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(self.clone())
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct SwitchController {}
+/// Hands control to the driver by asking for the path to be paused, ends
+/// once the path is resumed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwitchController {
+    switched: bool,
+}
+
+impl SwitchController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
 impl PathSegment for SwitchController {
     fn finished_transform(&self) -> bool {
         true
     }
 
-    fn start(&mut self, _: &crate::odometry::Odom, _: &mut crate::pid::Pid, pkt: &mut ToBrain) {}
+    fn start(&mut self, _: &crate::odometry::Odom, _: &mut crate::pid::Pid, pkt: &mut ToBrain) {
+        self.switched = false;
+    }
 
     fn follow(
         &mut self,
@@ -462,6 +1461,7 @@ impl PathSegment for SwitchController {
         _: &mut crate::pid::Pid,
         pkt: &mut ToBrain,
     ) -> crate::path::PathOutput {
+        self.switched = true;
         crate::path::PathOutput::SwitchToDriver
     }
     fn abrupt_end(&mut self, _odom: &Odom, pkt: &mut ToBrain) {}
@@ -470,11 +1470,18 @@ impl PathSegment for SwitchController {
         _: &crate::odometry::Odom,
         pkt: &mut ToBrain,
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
-        None
+        if self.switched {
+            Some(Vec::new())
+        } else {
+            None
+        }
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        Box::new(Self::new())
     }
 }
 
-fn optimise_target_heading(heading: f64, target: f64) -> f64 {
+pub(crate) fn optimise_target_heading(heading: f64, target: f64) -> f64 {
     let mut delta = target - heading;
     // map delta into [-TAU, TAU]
     delta %= TAU;
@@ -488,3 +1495,295 @@ fn optimise_target_heading(heading: f64, target: f64) -> f64 {
     }
     heading + delta
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::RobotConfig,
+        modifier_path::{
+            Check, Nop, ParallelEnd, ParallelSegment, RepeatSegment, TimedSegment, WaitUntil,
+            WhileSegment,
+        },
+        ports::PortRegistry,
+        sim,
+        trajectory::TrajectoryConfig,
+    };
+    use std::f64::consts::FRAC_PI_2;
+
+    fn config() -> RobotConfig {
+        RobotConfig::parse(include_str!("../robots/small.toml")).unwrap()
+    }
+
+    // uses a bit of every combinator, about 4.5s long
+    fn auton(ports: &PortRegistry) -> Path {
+        let intake = ports.claim_smart(5, "intake").unwrap();
+        let conveyor = ports.claim_smart(6, "conveyor").unwrap();
+        let spin = |motor: &SmartPortHandle, volts| {
+            PowerMotors::new(vec![motor.clone()], MotorControl::Voltage(volts))
+        };
+        let wait = |ms| TimedSegment::new(Box::new(Nop {}), Duration::from_millis(ms));
+        path!(
+            WhileSegment::new(
+                path!(
+                    Ram::new(0.5, Duration::from_millis(400)),
+                    RepeatSegment::new(
+                        Box::new(path!(
+                            Ram::new(-0.3, Duration::from_millis(200)),
+                            Ram::new(0.3, Duration::from_millis(200)),
+                        )),
+                        2,
+                    ),
+                ),
+                path!(spin(&intake, -5.0)),
+                true,
+            ),
+            ParallelSegment::new(
+                vec![
+                    path!(TurnTo::new(FRAC_PI_2)),
+                    path!(TimedSegment::new(
+                        Box::new(spin(&conveyor, 12.0)),
+                        Duration::from_millis(600),
+                    )),
+                ],
+                ParallelEnd::All,
+            ),
+            wait(300),
+            TimedSegment::new(
+                Box::new(Ram::new(-0.4, Duration::from_secs(5))),
+                Duration::from_millis(800),
+            ),
+        )
+    }
+
+    fn drivebase() -> Drivebase<3> {
+        config().drivebase(&PortRegistry::new()).unwrap()
+    }
+
+    fn run(path: &mut Path, ticks: usize) -> Vec<ToBrain> {
+        sim::run_path::<3>(&config(), path, ticks).unwrap()
+    }
+
+    fn assert_same_packets(expected: &[ToBrain], actual: &[ToBrain]) {
+        assert_eq!(sim::packet_runs(expected, 0), sim::packet_runs(actual, 0));
+    }
+
+    const TICKS: usize = 600;
+
+    #[test]
+    fn fresh_runs_match() {
+        let ports = PortRegistry::new();
+        let mut first = auton(&ports);
+        let first_out = run(&mut first, TICKS);
+        assert!(first.ended());
+        let mut second = auton(&PortRegistry::new());
+        assert_same_packets(&first_out, &run(&mut second, TICKS));
+    }
+
+    #[test]
+    fn clone_of_partly_followed_path_replays_from_the_start() {
+        let mut fresh = auton(&PortRegistry::new());
+        let expected = run(&mut fresh, TICKS);
+
+        let mut original = auton(&PortRegistry::new());
+        // stop part way through each of the combinators
+        for ticks in [20, 60, 100, 150, 290, 350, 400] {
+            let mut partial = original.clone();
+            run(&mut partial, ticks);
+            assert!(!partial.ended(), "{ticks} ticks should be part way");
+            assert_same_packets(&expected, &run(&mut partial.clone(), TICKS));
+        }
+        run(&mut original, TICKS);
+        assert!(original.ended());
+        let mut clone = original.clone();
+        assert_same_packets(&expected, &run(&mut clone, TICKS));
+        // and a clone of a clone
+        assert_same_packets(&expected, &run(&mut clone.clone(), TICKS));
+    }
+
+    #[test]
+    fn deeply_nested_paths_clone_in_linear_time() {
+        // each level used to copy its children twice, so this never finished
+        let mut path = Path::new(Vec::new());
+        for _ in 0..64 {
+            path = path!(path);
+        }
+        let mut copy = path.clone();
+        run(&mut copy, 100);
+        assert!(copy.ended());
+    }
+
+    #[test]
+    fn boxed_clone_resets_segments() {
+        let ports = PortRegistry::new();
+        let intake = ports.claim_smart(5, "intake").unwrap();
+        let segments: Vec<Box<dyn PathSegment>> = vec![
+            Box::new(Ram::new(0.5, Duration::from_millis(500))),
+            Box::new(TimedSegment::new(
+                Box::new(Ram::new(0.5, Duration::from_secs(2))),
+                Duration::from_millis(500),
+            )),
+            Box::new(RepeatSegment::new(
+                Box::new(Ram::new(0.3, Duration::from_millis(100))),
+                4,
+            )),
+            Box::new(WhileSegment::new(
+                path!(Ram::new(0.5, Duration::from_millis(500))),
+                path!(PowerMotors::new(vec![intake.clone()], MotorControl::Voltage(6.0))),
+                true,
+            )),
+            Box::new(ParallelSegment::new(
+                vec![
+                    path!(Ram::new(0.5, Duration::from_millis(300))),
+                    path!(Ram::new(0.5, Duration::from_millis(500))),
+                ],
+                ParallelEnd::Race,
+            )),
+        ];
+        for segment in segments {
+            let mut expected_path = Path::new(vec![segment.boxed_clone()]);
+            let expected = run(&mut expected_path, 100);
+            // the running copy inside the path is what gets cloned
+            let mut started = Path::new(vec![segment]);
+            run(&mut started, 20);
+            let mut clone = Path::new(vec![started
+                .current_segment
+                .as_ref()
+                .expect("segment should still be running")
+                .boxed_clone()]);
+            assert_same_packets(&expected, &run(&mut clone, 100));
+        }
+    }
+
+    #[test]
+    fn only_failures_stop_a_path() {
+        let ram = || Ram::new(0.5, Duration::from_millis(100));
+        let mut timed_out = path!(
+            WaitUntil::new(|_, _| false).timeout(Duration::from_millis(100)),
+            ram(),
+        );
+        let out = run(&mut timed_out, 40);
+        assert!(timed_out.ended());
+        assert_eq!(PathSegment::outcome(&timed_out), Outcome::TimedOut);
+        assert!(out.iter().any(|v| format!("{v:?}").contains("Voltage(6.0)")));
+
+        let mut failed = path!(Check::new("missed", |_, _| false), ram());
+        let out = run(&mut failed, 40);
+        assert!(failed.ended());
+        assert_eq!(
+            PathSegment::outcome(&failed),
+            Outcome::Failed("missed".to_owned())
+        );
+        assert!(!out.iter().any(|v| format!("{v:?}").contains("Voltage(6.0)")));
+    }
+
+    #[test]
+    fn pure_pursuit_keeps_to_the_nearer_leg() {
+        // a dense hairpin, the way back passes 200mm from the start
+        let out = (0..=20).map(|i| Vec2::new(i as f64 * 50.0, 0.0));
+        let back = (0..=30).map(|i| Vec2::new(1000.0 - i as f64 * 50.0, 200.0));
+        let mut pp = PurePursuitPath::new(out.chain(back), &drivebase());
+        let target = pp.lookahead_point(Vec2::ZERO);
+        assert!((target - Vec2::new(300.0, 0.0)).mag() < 1.0, "{target:?}");
+        assert!(!pp.on_last_segment());
+        // moving on a bit only moves the point a bit
+        let target = pp.lookahead_point(Vec2::new(100.0, 0.0));
+        assert!((target - Vec2::new(400.0, 0.0)).mag() < 1.0, "{target:?}");
+    }
+
+    #[test]
+    fn pure_pursuit_loop_runs_to_the_end() {
+        let square = [
+            Vec2::ZERO,
+            Vec2::new(800.0, 0.0),
+            Vec2::new(800.0, 800.0),
+            Vec2::new(0.0, 800.0),
+            Vec2::ZERO,
+        ];
+        let drivebase = drivebase();
+        let follow = |ticks| {
+            let mut path = path!(PurePursuitPath::new(square, &drivebase));
+            run(&mut path, ticks);
+            path.ended()
+        };
+        assert!(!follow(50), "ended where the loop starts");
+        assert!(follow(1000));
+    }
+
+    #[test]
+    fn trajectory_path_reaches_its_goal() {
+        // 600mm straight then a quarter circle of radius 500 to the left
+        let straight = (0..=30).map(|i| (Vec2::new(i as f64 * 20.0, 0.0), 0.0));
+        let arc = (1..=40).map(|i| {
+            let a = i as f64 / 40.0 * FRAC_PI_2;
+            (Vec2::new(600.0 + 500.0 * a.sin(), 500.0 - 500.0 * a.cos()), a)
+        });
+        let waypoints: Vec<_> = straight.chain(arc).collect();
+        let limits = TrajectoryConfig::new(700.0, 800.0).max_centripetal(300.0);
+        let trajectory = Trajectory::generate(&waypoints, limits);
+        let mut path = path!(
+            TrajectoryPath::new(trajectory, TrajectoryGains::default(), &drivebase()),
+            Check::new("missed the goal", |odom, _| {
+                (odom.pos() - Vec2::new(1100.0, 500.0)).mag() < 50.0
+                    && (odom.heading() - FRAC_PI_2).abs() < 0.15
+            }),
+        );
+        run(&mut path, 400);
+        assert!(path.ended());
+        assert_eq!(PathSegment::outcome(&path), Outcome::Succeeded);
+    }
+
+    #[test]
+    fn turn_to_point_on_the_robot_does_not_turn() {
+        let moved = |out: &[ToBrain]| {
+            out.iter()
+                .any(|pkt| matches!(pkt.set_motors[0], MotorControl::Voltage(v) if v != 0.0))
+        };
+        let mut near = path!(TurnToPoint::new(Vec2::new(0.0, -5.0)));
+        assert!(!moved(&run(&mut near, 50)));
+        assert!(near.ended());
+        let mut far = path!(TurnToPoint::new(Vec2::new(0.0, -50.0)));
+        assert!(moved(&run(&mut far, 50)));
+    }
+
+    #[test]
+    fn ramsete_point_reaches_its_target() {
+        let mut path = path!(RamsetePoint::new(
+            (Vec2::new(600.0, 200.0), 0.5),
+            Ramsete::new(0.025, 0.7),
+        ));
+        let out = run(&mut path, 500);
+        assert!(path.ended());
+        assert!(out
+            .iter()
+            .any(|pkt| matches!(pkt.set_motors[0], MotorControl::Velocity(_))));
+    }
+
+    #[test]
+    fn parallel_drive_goes_to_the_first_busy_child() {
+        let ports = PortRegistry::new();
+        let intake = ports.claim_smart(5, "intake").unwrap();
+        let drive_still = |out: &[ToBrain]| {
+            out.iter().all(|pkt| {
+                [0, 7]
+                    .iter()
+                    .all(|&i| !matches!(pkt.set_motors[i], MotorControl::Voltage(v) if v != 0.0))
+            })
+        };
+
+        // a mechanism doesn't take the drivetrain from a later child
+        let mut path = path!(ParallelSegment::race(vec![
+            path!(PowerMotors::new(vec![intake], MotorControl::Voltage(6.0))),
+            path!(Ram::new(0.5, Duration::from_millis(200))),
+        ]));
+        assert!(!drive_still(&run(&mut path, 10)));
+
+        // a turn holding still does, and the other turn has its own target
+        let mut path = path!(ParallelSegment::all(vec![
+            path!(TurnTo::new(0.0)),
+            path!(TurnTo::new(FRAC_PI_2)),
+            path!(Ram::new(0.5, Duration::from_millis(200))),
+        ]));
+        assert!(drive_still(&run(&mut path, 15)));
+    }
+}
@@ -1,5 +1,8 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use crate::clock;
+
+#[derive(Debug, Clone)]
 pub struct Pid {
     pub kp: f64,
     pub ki: f64,
@@ -20,7 +23,7 @@ impl Pid {
             target: 0.0,
             ki_integral: 0.0,
             last_error: 0.0,
-            last_update: Instant::now(),
+            last_update: clock::now(),
             first_update: true,
         }
     }
@@ -28,7 +31,7 @@ impl Pid {
         self.target = target;
     }
     pub fn poll(&mut self, pv: f64) -> f64 {
-        let now = Instant::now();
+        let now = clock::now();
         let diff_t = now.duration_since(self.last_update).as_secs_f64();
 
         let error = self.target - pv;
@@ -50,11 +53,15 @@ impl Pid {
 
         output
     }
+    /// Pushes the last update forward so time spent paused isn't integrated.
+    pub fn shift_time(&mut self, by: Duration) {
+        self.last_update += by;
+    }
     pub fn reset(&mut self) {
         log::info!("reset called");
         self.first_update = true;
         self.ki_integral = 0.0;
         self.last_error = 0.0;
-        self.last_update = Instant::now();
+        self.last_update = clock::now();
     }
 }
@@ -0,0 +1,447 @@
+use std::{cell::RefCell, panic::Location, rc::Rc};
+
+use robot_serial::protocol::{ConfigureAdiPort, EncoderState, MotorControl, ToBrain, ToRobot};
+
+pub const SMART_PORT_COUNT: usize = 21;
+pub const ADI_PORT_COUNT: usize = 8;
+
+/// A smart port number as printed on the brain, only constructible in
+/// `1..=21`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SmartPort(u8);
+
+impl SmartPort {
+    pub const fn new(port: usize) -> Option<Self> {
+        if port >= 1 && port <= SMART_PORT_COUNT {
+            Some(Self(port as u8))
+        } else {
+            None
+        }
+    }
+    /// The port number as printed on the brain.
+    pub const fn number(self) -> usize {
+        self.0 as usize
+    }
+    /// Index of the port in packet arrays such as `ToBrain::set_motors`.
+    pub const fn index(self) -> usize {
+        self.0 as usize - 1
+    }
+}
+
+impl std::fmt::Display for SmartPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An ADI (triport) port number as printed on the brain, only constructible
+/// in `1..=8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AdiPort(u8);
+
+impl AdiPort {
+    pub const fn new(port: usize) -> Option<Self> {
+        if port >= 1 && port <= ADI_PORT_COUNT {
+            Some(Self(port as u8))
+        } else {
+            None
+        }
+    }
+    /// The port number as printed on the brain.
+    pub const fn number(self) -> usize {
+        self.0 as usize
+    }
+    /// Index of the port in packet arrays such as `ToBrain::set_triports`.
+    pub const fn index(self) -> usize {
+        self.0 as usize - 1
+    }
+}
+
+impl std::fmt::Display for AdiPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortKind {
+    Smart,
+    Adi,
+}
+
+impl std::fmt::Display for PortKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Smart => write!(f, "smart port"),
+            Self::Adi => write!(f, "ADI port"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PortError {
+    OutOfRange {
+        kind: PortKind,
+        port: usize,
+        owner: String,
+    },
+    AlreadyClaimed {
+        kind: PortKind,
+        port: usize,
+        owner: String,
+        claimed_by: String,
+    },
+}
+
+impl std::fmt::Display for PortError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange { kind, port, owner } => {
+                write!(f, "{owner} uses {kind} {port} which does not exist")
+            }
+            Self::AlreadyClaimed {
+                kind,
+                port,
+                owner,
+                claimed_by,
+            } => write!(
+                f,
+                "{owner} uses {kind} {port} already claimed by {claimed_by}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PortError {}
+
+// writes to a port since the last `finish_tick`
+#[derive(Debug)]
+struct TickWrites {
+    owner: Rc<str>,
+    // each place in the code the port was written from, once
+    sites: Vec<&'static Location<'static>>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    smart: [Option<Rc<str>>; SMART_PORT_COUNT],
+    adi: [Option<Rc<str>>; ADI_PORT_COUNT],
+    last_smart_writer: [Option<Rc<str>>; SMART_PORT_COUNT],
+    last_adi_writer: [Option<Rc<str>>; ADI_PORT_COUNT],
+    // kept per port so a loop that never calls `finish_tick` doesn't grow
+    smart_tick_writes: [Option<TickWrites>; SMART_PORT_COUNT],
+    adi_tick_writes: [Option<TickWrites>; ADI_PORT_COUNT],
+}
+
+impl Registry {
+    fn claims(&mut self, kind: PortKind) -> &mut [Option<Rc<str>>] {
+        match kind {
+            PortKind::Smart => &mut self.smart,
+            PortKind::Adi => &mut self.adi,
+        }
+    }
+    fn last_writers(&mut self, kind: PortKind) -> &mut [Option<Rc<str>>] {
+        match kind {
+            PortKind::Smart => &mut self.last_smart_writer,
+            PortKind::Adi => &mut self.last_adi_writer,
+        }
+    }
+    fn tick_writes(&mut self, kind: PortKind) -> &mut [Option<TickWrites>] {
+        match kind {
+            PortKind::Smart => &mut self.smart_tick_writes,
+            PortKind::Adi => &mut self.adi_tick_writes,
+        }
+    }
+}
+
+/// Hands out each smart and ADI port to a single owner and keeps track of
+/// who writes to them. Clones share the same registry.
+///
+/// Handles can be cloned so several subsystems can share one (the auton and
+/// driver control both run the intake), so writes are also told apart by
+/// the line of code they come from. A port written from two places in one
+/// tick is warned about as the second write silently wins.
+#[derive(Debug, Clone, Default)]
+pub struct PortRegistry(Rc<RefCell<Registry>>);
+
+impl PortRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // `port` has already been range checked by `SmartPort` or `AdiPort`
+    fn claim(&self, kind: PortKind, port: usize, owner: &str) -> Result<Rc<str>, PortError> {
+        let mut registry = self.0.borrow_mut();
+        let claim = &mut registry.claims(kind)[port - 1];
+        if let Some(claimed_by) = claim {
+            return Err(PortError::AlreadyClaimed {
+                kind,
+                port,
+                owner: owner.to_owned(),
+                claimed_by: claimed_by.to_string(),
+            });
+        }
+        let owner: Rc<str> = owner.into();
+        *claim = Some(owner.clone());
+        Ok(owner)
+    }
+    pub fn claim_smart(&self, port: usize, owner: &str) -> Result<SmartPortHandle, PortError> {
+        let Some(port) = SmartPort::new(port) else {
+            return Err(PortError::OutOfRange {
+                kind: PortKind::Smart,
+                port,
+                owner: owner.to_owned(),
+            });
+        };
+        Ok(SmartPortHandle {
+            owner: self.claim(PortKind::Smart, port.number(), owner)?,
+            port,
+            registry: self.clone(),
+        })
+    }
+    pub fn claim_adi(&self, port: usize, owner: &str) -> Result<AdiPortHandle, PortError> {
+        let Some(port) = AdiPort::new(port) else {
+            return Err(PortError::OutOfRange {
+                kind: PortKind::Adi,
+                port,
+                owner: owner.to_owned(),
+            });
+        };
+        Ok(AdiPortHandle {
+            owner: self.claim(PortKind::Adi, port.number(), owner)?,
+            port,
+            registry: self.clone(),
+        })
+    }
+    pub fn owner(&self, kind: PortKind, port: usize) -> Option<Rc<str>> {
+        let mut registry = self.0.borrow_mut();
+        registry.claims(kind).get(port.checked_sub(1)?)?.clone()
+    }
+    /// Owner of the handle that last wrote to `port` in a finished tick.
+    pub fn last_writer(&self, kind: PortKind, port: usize) -> Option<Rc<str>> {
+        let mut registry = self.0.borrow_mut();
+        registry
+            .last_writers(kind)
+            .get(port.checked_sub(1)?)?
+            .clone()
+    }
+    fn record_write(
+        &self,
+        kind: PortKind,
+        port: usize,
+        owner: &Rc<str>,
+        site: &'static Location<'static>,
+    ) {
+        let mut registry = self.0.borrow_mut();
+        let writes = registry.tick_writes(kind)[port - 1].get_or_insert_with(|| TickWrites {
+            owner: owner.clone(),
+            sites: Vec::new(),
+        });
+        writes.owner = owner.clone();
+        if !writes.sites.contains(&site) {
+            writes.sites.push(site);
+        }
+    }
+    /// Should be called once per loop before the packet is sent. Warns about
+    /// ports written from more than one place in the code during the tick.
+    pub fn finish_tick(&self) {
+        for warning in self.end_tick() {
+            log::warn!("{warning}");
+        }
+    }
+    // moves the tick's writes into the last writers, returning a warning for
+    // each port written from more than one place
+    fn end_tick(&self) -> Vec<String> {
+        let mut registry = self.0.borrow_mut();
+        let mut warnings = Vec::new();
+        for kind in [PortKind::Smart, PortKind::Adi] {
+            for idx in 0..registry.tick_writes(kind).len() {
+                let Some(TickWrites { owner, sites }) = registry.tick_writes(kind)[idx].take()
+                else {
+                    continue;
+                };
+                let port = idx + 1;
+                if sites.len() > 1 {
+                    let sites: Vec<_> = sites.iter().map(ToString::to_string).collect();
+                    warnings.push(format!(
+                        "{kind} {port} ({owner}) written from {} in one tick",
+                        sites.join(", ")
+                    ));
+                }
+                log::trace!("{kind} {port} last written by {owner}");
+                registry.last_writers(kind)[idx] = Some(owner);
+            }
+        }
+        warnings
+    }
+}
+
+/// A claimed smart port, cloning it shares the claim.
+#[derive(Clone)]
+pub struct SmartPortHandle {
+    port: SmartPort,
+    owner: Rc<str>,
+    registry: PortRegistry,
+}
+
+impl SmartPortHandle {
+    pub fn port(&self) -> SmartPort {
+        self.port
+    }
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+    #[track_caller]
+    pub fn set_motor(&self, pkt: &mut ToBrain, control: MotorControl) {
+        pkt.set_motors[self.port.index()] = control;
+        self.registry.record_write(
+            PortKind::Smart,
+            self.port.number(),
+            &self.owner,
+            Location::caller(),
+        );
+    }
+    pub fn reversed(self, reversed: bool) -> Motor {
+        Motor::new(self, reversed)
+    }
+}
+
+impl std::fmt::Debug for SmartPortHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SmartPort({} {})", self.port, self.owner)
+    }
+}
+
+/// A claimed ADI (triport) port, cloning it shares the claim.
+#[derive(Clone)]
+pub struct AdiPortHandle {
+    port: AdiPort,
+    owner: Rc<str>,
+    registry: PortRegistry,
+}
+
+impl AdiPortHandle {
+    pub fn port(&self) -> AdiPort {
+        self.port
+    }
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+    #[track_caller]
+    pub fn set_triport(&self, pkt: &mut ToBrain, state: ConfigureAdiPort) {
+        pkt.set_triports[self.port.index()] = state;
+        self.registry.record_write(
+            PortKind::Adi,
+            self.port.number(),
+            &self.owner,
+            Location::caller(),
+        );
+    }
+}
+
+impl std::fmt::Debug for AdiPortHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AdiPort({} {})", self.port, self.owner)
+    }
+}
+
+/// A motor that may be mounted backwards. Commands and encoder readings are
+/// flipped for reversed motors so callers can treat every motor as forward.
+#[derive(Debug, Clone)]
+pub struct Motor {
+    port: SmartPortHandle,
+    reversed: bool,
+}
+
+impl Motor {
+    pub fn new(port: SmartPortHandle, reversed: bool) -> Self {
+        Self { port, reversed }
+    }
+    pub fn port(&self) -> &SmartPortHandle {
+        &self.port
+    }
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+    #[track_caller]
+    pub fn set(&self, pkt: &mut ToBrain, control: MotorControl) {
+        let control = match control {
+            MotorControl::Voltage(v) if self.reversed => MotorControl::Voltage(-v),
+            MotorControl::Velocity(v) if self.reversed => MotorControl::Velocity(-v),
+            other => other,
+        };
+        self.port.set_motor(pkt, control);
+    }
+    /// Encoder position in radians, `None` if the motor isn't reporting.
+    pub fn radians(&self, pkt: &ToRobot) -> Option<f64> {
+        let EncoderState::Radians(radians) = pkt.encoder_state[self.port.port().index()] else {
+            return None;
+        };
+        Some(if self.reversed { -radians } else { radians })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_claimed_once() {
+        let ports = PortRegistry::new();
+        let intake = ports.claim_smart(5, "intake").unwrap();
+        let err = ports.claim_smart(5, "conveyor").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "conveyor uses smart port 5 already claimed by intake"
+        );
+        // the same number on the other kind of port is fine
+        ports.claim_adi(5, "latch").unwrap();
+        assert!(matches!(
+            ports.claim_smart(22, "arm"),
+            Err(PortError::OutOfRange { port: 22, .. })
+        ));
+        assert_eq!(ports.owner(PortKind::Smart, 5).as_deref(), Some("intake"));
+        drop(intake);
+        assert!(ports.claim_smart(5, "conveyor").is_err());
+    }
+
+    #[test]
+    fn writes_from_two_places_warn() {
+        let ports = PortRegistry::new();
+        let auton = ports.claim_smart(5, "intake").unwrap();
+        let driver = auton.clone();
+        let mut pkt = ToBrain::default();
+
+        // one place writing every tick is fine, however often
+        for _ in 0..3 {
+            auton.set_motor(&mut pkt, MotorControl::Voltage(6.0));
+        }
+        assert!(ports.end_tick().is_empty());
+        assert_eq!(
+            ports.last_writer(PortKind::Smart, 5).as_deref(),
+            Some("intake")
+        );
+
+        auton.set_motor(&mut pkt, MotorControl::Voltage(6.0));
+        driver.set_motor(&mut pkt, MotorControl::Voltage(-6.0));
+        let warnings = ports.end_tick();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("smart port 5 (intake) written from src/ports.rs"));
+
+        // the warning is only for the tick it happened in
+        driver.set_motor(&mut pkt, MotorControl::Voltage(-6.0));
+        assert!(ports.end_tick().is_empty());
+    }
+
+    #[test]
+    fn writes_without_finish_tick_stay_bounded() {
+        let ports = PortRegistry::new();
+        let latch = ports.claim_adi(1, "latch").unwrap();
+        let mut pkt = ToBrain::default();
+        for _ in 0..1000 {
+            latch.set_triport(&mut pkt, ConfigureAdiPort::DigitalHigh);
+        }
+        let registry = ports.0.borrow();
+        let writes = registry.adi_tick_writes[0].as_ref().unwrap();
+        assert_eq!(writes.sites.len(), 1);
+    }
+}
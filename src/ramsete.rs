@@ -1,4 +1,6 @@
-use crate::{odometry::Odom, vec::Vec2};
+use crate::{
+    odometry::Odom, path::optimise_target_heading, trajectory::TrajectoryState, vec::Vec2,
+};
 
 // see https://wiki.purduesigbots.com/software/control-algorithms/ramsete
 #[derive(Debug, Clone)]
@@ -47,3 +49,53 @@ impl Ramsete {
         Vec2::new(2.0 * linear_vel, angular_vel)
     }
 }
+
+/// Ramsete gains for `TrajectoryPath`, kept apart from `Ramsete` whose
+/// `beta` is tuned for `output_linear_angular`. `beta` is in rad²/mm² and
+/// `zeta` is unitless (0 to 1), so the usual 2.0 and 0.7 for metres are
+/// 2e-6 and 0.7 here, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryGains {
+    beta: f64,
+    zeta: f64,
+}
+
+impl Default for TrajectoryGains {
+    fn default() -> Self {
+        Self::new(2e-6, 0.7)
+    }
+}
+
+impl TrajectoryGains {
+    pub fn new(beta: f64, zeta: f64) -> Self {
+        Self { beta, zeta }
+    }
+    /// Linear (mm/s) and angular (rad/s) velocity to follow a trajectory,
+    /// the state's velocities as feedforward plus a correction for the
+    /// error to its pose.
+    pub fn output(&self, odom: &Odom, state: &TrajectoryState) -> Vec2 {
+        let (target_pos, target_heading) = state.pose;
+        let heading = odom.heading();
+        let (s, c) = heading.sin_cos();
+        let error = target_pos - odom.pos();
+        // error in the robot's frame, x forwards
+        let error_x = error.x * c + error.y * s;
+        let error_y = -error.x * s + error.y * c;
+        let error_heading = optimise_target_heading(heading, target_heading) - heading;
+
+        let v = state.velocity;
+        let w = state.angular_velocity;
+        let k = 2.0 * self.zeta * (w.powi(2) + self.beta * v.powi(2)).sqrt();
+        // sin(x)/x, which goes to 1 as x goes to 0
+        let sinc = if error_heading.abs() < 1e-9 {
+            1.0
+        } else {
+            error_heading.sin() / error_heading
+        };
+
+        Vec2::new(
+            v * error_heading.cos() + k * error_x,
+            w + k * error_heading + self.beta * v * sinc * error_y,
+        )
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use robot_serial::protocol::{ToBrain, ToRobot};
+
+use crate::{
+    brain::BrainIo,
+    clock::{self, ManualClock},
+};
+
+// Log layout: the magic header followed by entries of
+// [kind: u8][micros since start: u64 LE][len: u32 LE][postcard payload]
+const MAGIC: &[u8; 8] = b"FCRLOG01";
+const KIND_TO_ROBOT: u8 = 0;
+const KIND_TO_BRAIN: u8 = 1;
+// packets are well under this, anything bigger is a corrupt length
+const MAX_PAYLOAD: u32 = 64 * 1024;
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    Encoding(postcard::Error),
+    BadHeader,
+    UnknownEntry(u8),
+    EntryTooLarge(u32),
+    LimitReached,
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Encoding(e) => write!(f, "encoding error: {e}"),
+            Self::BadHeader => write!(f, "not a brain traffic log"),
+            Self::UnknownEntry(kind) => write!(f, "unknown log entry kind {kind}"),
+            Self::EntryTooLarge(len) => write!(f, "log entry of {len} bytes is too large"),
+            Self::LimitReached => write!(f, "log size limit reached"),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<postcard::Error> for RecordError {
+    fn from(e: postcard::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Entry {
+    ToRobot(Box<ToRobot>),
+    ToBrain(Box<ToBrain>),
+}
+
+/// Writes timestamped brain traffic to a compact binary log.
+pub struct Recorder<W: Write = BufWriter<File>> {
+    out: W,
+    start: Instant,
+    // bytes written so far and the most allowed
+    size: u64,
+    limit: Option<u64>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W) -> Result<Self, RecordError> {
+        out.write_all(MAGIC)?;
+        Ok(Self {
+            out,
+            start: clock::now(),
+            size: MAGIC.len() as u64,
+            limit: None,
+        })
+    }
+    /// Keeps the log under `bytes`, entries that would go over fail with
+    /// `RecordError::LimitReached` and aren't written.
+    pub fn limit(mut self, bytes: u64) -> Self {
+        self.limit = Some(bytes);
+        self
+    }
+    pub fn record_to_robot(&mut self, pkt: &ToRobot) -> Result<(), RecordError> {
+        let payload = postcard::to_stdvec(pkt)?;
+        self.write_entry(KIND_TO_ROBOT, &payload)
+    }
+    pub fn record_to_brain(&mut self, pkt: &ToBrain) -> Result<(), RecordError> {
+        let payload = postcard::to_stdvec(pkt)?;
+        self.write_entry(KIND_TO_BRAIN, &payload)
+    }
+    fn write_entry(&mut self, kind: u8, payload: &[u8]) -> Result<(), RecordError> {
+        let entry_size = (1 + 8 + 4 + payload.len()) as u64;
+        if self.limit.is_some_and(|v| self.size + entry_size > v) {
+            return Err(RecordError::LimitReached);
+        }
+        self.size += entry_size;
+        let micros = clock::elapsed(self.start).as_micros() as u64;
+        self.out.write_all(&[kind])?;
+        self.out.write_all(&micros.to_le_bytes())?;
+        self.out.write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.out.write_all(payload)?;
+        Ok(())
+    }
+    pub fn flush(&mut self) -> Result<(), RecordError> {
+        Ok(self.out.flush()?)
+    }
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Reads back the entries written by a `Recorder`.
+pub struct LogReader<R: Read = BufReader<File>> {
+    input: R,
+}
+
+impl LogReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> LogReader<R> {
+    pub fn new(mut input: R) -> Result<Self, RecordError> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(RecordError::BadHeader);
+        }
+        Ok(Self { input })
+    }
+    /// Returns `Ok(None)` once the end of the log is reached.
+    pub fn next_entry(&mut self) -> Result<Option<(Duration, Entry)>, RecordError> {
+        let mut kind = [0; 1];
+        match self.input.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut micros = [0; 8];
+        self.input.read_exact(&mut micros)?;
+        let mut len = [0; 4];
+        self.input.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_PAYLOAD {
+            return Err(RecordError::EntryTooLarge(len));
+        }
+        let mut payload = vec![0; len as usize];
+        self.input.read_exact(&mut payload)?;
+
+        let timestamp = Duration::from_micros(u64::from_le_bytes(micros));
+        let entry = match kind[0] {
+            KIND_TO_ROBOT => Entry::ToRobot(postcard::from_bytes(&payload)?),
+            KIND_TO_BRAIN => Entry::ToBrain(postcard::from_bytes(&payload)?),
+            other => return Err(RecordError::UnknownEntry(other)),
+        };
+        Ok(Some((timestamp, entry)))
+    }
+    pub fn read_all(mut self) -> Result<Vec<(Duration, Entry)>, RecordError> {
+        let mut entries = Vec::new();
+        while let Some(entry) = self.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Feeds the `ToRobot` packets of a recorded session back through a `Brain`.
+///
+/// In real time mode packets are released once as much time has passed as
+/// when they were recorded, otherwise every read returns the next packet.
+/// For an exact replay give it a `ManualClock` with `with_clock`, which is
+/// moved to each packet's recorded time as it is read. Packets written by
+/// the loop are kept so they can be compared against `recorded_writes`.
+#[derive(Debug)]
+pub struct Replay {
+    reads: VecDeque<(Duration, ToRobot)>,
+    recorded_writes: Vec<(Duration, ToBrain)>,
+    written: Vec<ToBrain>,
+    real_time: bool,
+    start: Option<Instant>,
+    // and the clock's time when it was given, which log times count from
+    clock: Option<(ManualClock, Duration)>,
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P, real_time: bool) -> Result<Self, RecordError> {
+        Ok(Self::from_entries(
+            LogReader::open(path)?.read_all()?,
+            real_time,
+        ))
+    }
+    pub fn from_entries(entries: Vec<(Duration, Entry)>, real_time: bool) -> Self {
+        let mut reads = VecDeque::new();
+        let mut recorded_writes = Vec::new();
+        for (t, entry) in entries {
+            match entry {
+                Entry::ToRobot(pkt) => reads.push_back((t, *pkt)),
+                Entry::ToBrain(pkt) => recorded_writes.push((t, *pkt)),
+            }
+        }
+        Self {
+            reads,
+            recorded_writes,
+            written: Vec::new(),
+            real_time,
+            start: None,
+            clock: None,
+        }
+    }
+    /// Advances `clock` to the recorded time of every packet read, counting
+    /// from now. With the same clock installed through `clock::set_clock`
+    /// the control code sees the recorded timing.
+    pub fn with_clock(mut self, clock: ManualClock) -> Self {
+        let start = clock.elapsed();
+        self.clock = Some((clock, start));
+        self
+    }
+    pub fn finished(&self) -> bool {
+        self.reads.is_empty()
+    }
+    pub fn recorded_writes(&self) -> &[(Duration, ToBrain)] {
+        &self.recorded_writes
+    }
+    pub fn written(&self) -> &[ToBrain] {
+        &self.written
+    }
+}
+
+impl BrainIo for Replay {
+    fn read_packet(&mut self) -> Result<ToRobot, robot_serial::Error> {
+        let Some((t, _)) = self.reads.front() else {
+            return Err(robot_serial::Error::NoPacketRead);
+        };
+        if let Some((clock, start)) = &self.clock {
+            clock.advance((*start + *t).saturating_sub(clock.elapsed()));
+        } else if self.real_time {
+            let start = *self.start.get_or_insert_with(clock::now);
+            if clock::elapsed(start) < *t {
+                return Err(robot_serial::Error::NoPacketRead);
+            }
+        }
+        Ok(self.reads.pop_front().unwrap().1)
+    }
+    fn write_packet(&mut self, pkt: &ToBrain) -> Result<(), robot_serial::Error> {
+        self.written.push(pkt.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        brain::Brain, config::RobotConfig, odometry::Odom, ports::PortRegistry, sim, vec::Vec2,
+    };
+
+    const TICKS: usize = 200;
+
+    // runs a loop like the main one, driving a curve, until the packets run
+    // out and returns the odometry pose after every packet
+    fn drive<B: BrainIo>(io: B, first: [ToRobot; 2], record_to: Option<&Path>) -> Vec<(Vec2, f64)> {
+        let config = RobotConfig::parse(include_str!("../robots/small.toml")).unwrap();
+        let ports = PortRegistry::new();
+        let mut drivebase = config.drivebase::<3>(&ports).unwrap();
+        let mut imu = config.imu(&ports).unwrap();
+        let (mut brain, mut controller) = Brain::with_packets(io, first.clone());
+        imu.update(&first[0]);
+        drivebase.update(&first[0]);
+        let mut odom = Odom::new(Vec2::ZERO, 0.0, &imu, &drivebase);
+        if let Some(path) = record_to {
+            brain.record_to(Recorder::create(path).unwrap());
+        }
+
+        let mut poses = Vec::new();
+        for _ in 0..TICKS {
+            let (pkt, read) = brain.update_state(&mut controller);
+            if !read {
+                break;
+            }
+            imu.update(&pkt);
+            drivebase.update(&pkt);
+            odom.update(&imu, &drivebase, &pkt);
+            drivebase.write_voltage(0.4, 0.7, brain.get_brain_pkt());
+            brain.write_changes();
+            poses.push((odom.pos(), odom.heading()));
+        }
+        brain.stop_recording();
+        poses
+    }
+
+    #[test]
+    fn replay_reproduces_odometry() {
+        let log = std::env::temp_dir().join(format!("replay-test-{}.log", std::process::id()));
+        let config = RobotConfig::parse(include_str!("../robots/small.toml")).unwrap();
+
+        let clock = ManualClock::new();
+        clock::set_clock(clock.clone());
+        let mut template = ToRobot::default();
+        template.imu_state[config.imu.port - 1] = robot_serial::protocol::ImuState::State {
+            x_rotation: 0.0,
+            y_rotation: 0.0,
+            z_rotation: 0.0,
+        };
+        let mut drive_sim =
+            sim::DriveSim::new(sim::DriveSimConfig::for_robot(&config), template).with_clock(clock);
+        let first = [
+            drive_sim.read_packet().unwrap(),
+            drive_sim.read_packet().unwrap(),
+        ];
+        let recorded = drive(drive_sim, first.clone(), Some(&log));
+
+        let replay = Replay::open(&log, false).unwrap();
+        std::fs::remove_file(&log).unwrap();
+        let clock = ManualClock::new();
+        clock::set_clock(clock.clone());
+        let replayed = drive(replay.with_clock(clock), first, None);
+        clock::reset_clock();
+
+        assert_eq!(recorded.len(), TICKS);
+        assert_ne!(recorded.first(), recorded.last());
+        assert_eq!(recorded, replayed);
+    }
+
+    #[test]
+    fn corrupt_length_is_an_error() {
+        let mut log = MAGIC.to_vec();
+        log.push(KIND_TO_ROBOT);
+        log.extend(0u64.to_le_bytes());
+        log.extend(u32::MAX.to_le_bytes());
+        let mut reader = LogReader::new(log.as_slice()).unwrap();
+        assert!(matches!(
+            reader.next_entry(),
+            Err(RecordError::EntryTooLarge(u32::MAX))
+        ));
+    }
+}
@@ -0,0 +1,579 @@
+//! Text format for autons so they can be changed without a rebuild.
+//!
+//! Statements are separated by newlines or `;` and `#` starts a comment.
+//!
+//! ```text
+//! turn_to 135deg
+//! ram -0.2 1500ms
+//! power_side 96                   # add `reversed` to spin the other way
+//! latch back release; latch front grab
+//! power intake,conveyor -12V      # or coast / brake / hold
+//! wait 1s
+//! timed 3000ms { power conveyor -12V }
+//! repeat 2 { ram 0.5 500ms; ram -0.5 500ms }
+//! while { ram 0.5 560ms } { power intake -5V }
+//! parallel race { turn_to 90deg } { power intake -12V }   # or all / deadline
+//! ramsete { 0 10 90deg; 0 500 90deg }
+//! driver
+//! ```
+//!
+//! Angles need a `deg` or `rad` suffix and durations an `ms` or `s` suffix.
+//! For `parallel deadline` the first block is the deadline.
+
+use std::{collections::HashMap, path::Path as FsPath, time::Duration};
+
+use robot_serial::protocol::MotorControl;
+
+use crate::{
+    auton::AutonRegistry,
+    latch::{Latch, LatchAction},
+    modifier_path::{Nop, ParallelEnd, ParallelSegment, RepeatSegment, TimedSegment, WhileSegment},
+    path::{Path, PathSegment, PowerMotors, PowerSide, Ram, RamsetePath, SwitchController, TurnTo},
+    ports::SmartPortHandle,
+    ramsete::Ramsete,
+    vec::Vec2,
+};
+
+/// Mechanisms that scripts can refer to by name.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptEnv {
+    latches: HashMap<String, Latch>,
+    motors: HashMap<String, SmartPortHandle>,
+    ramsete: Option<Ramsete>,
+}
+
+impl ScriptEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn latch(mut self, name: &str, latch: Latch) -> Self {
+        self.latches.insert(name.to_owned(), latch);
+        self
+    }
+    pub fn motor(mut self, name: &str, motor: SmartPortHandle) -> Self {
+        self.motors.insert(name.to_owned(), motor);
+        self
+    }
+    pub fn ramsete(mut self, ramsete: Ramsete) -> Self {
+        self.ramsete = Some(ramsete);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read script: {e}"),
+            Self::Parse(e) => write!(f, "failed to parse script: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<std::io::Error> for ScriptError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ParseError> for ScriptError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+pub fn parse(src: &str, env: &ScriptEnv) -> Result<Path, ParseError> {
+    let tokens = tokenize(src);
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        env,
+        last_line: src.lines().count().max(1),
+    };
+    let segments = parser.body()?;
+    if let Some(tok) = parser.peek() {
+        return Err(parser.error_at(tok.line, "unexpected `}`"));
+    }
+    Ok(Path::new(segments))
+}
+
+pub fn load<P: AsRef<FsPath>>(path: P, env: &ScriptEnv) -> Result<Path, ScriptError> {
+    Ok(parse(&std::fs::read_to_string(path)?, env)?)
+}
+
+/// Registers every `.auton` file in `dir` under its file name. Scripts are
+/// read again each time the auton is built so edits apply without a restart,
+/// a script that fails to load runs nothing.
+pub fn register_dir<P: AsRef<FsPath>>(
+    registry: &mut AutonRegistry,
+    dir: P,
+    env: &ScriptEnv,
+) -> std::io::Result<()> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "auton"))
+        .collect();
+    files.sort();
+    for file in files {
+        let Some(name) = file.file_stem().map(|v| v.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let env = env.clone();
+        registry.add(&name, move || match load(&file, &env) {
+            Ok(path) => path,
+            Err(e) => {
+                log::error!("{}: {e}", file.display());
+                Path::new(Vec::new())
+            }
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Open,
+    Close,
+    Separator,
+}
+
+#[derive(Debug, Clone)]
+struct Tok {
+    token: Token,
+    line: usize,
+}
+
+fn tokenize(src: &str) -> Vec<Tok> {
+    let mut tokens = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut word = String::new();
+        let push_word = |word: &mut String, tokens: &mut Vec<Tok>| {
+            if !word.is_empty() {
+                tokens.push(Tok {
+                    token: Token::Word(std::mem::take(word)),
+                    line: line_no,
+                });
+            }
+        };
+        for c in line.chars() {
+            let token = match c {
+                '{' => Token::Open,
+                '}' => Token::Close,
+                ';' => Token::Separator,
+                c if c.is_whitespace() => {
+                    push_word(&mut word, &mut tokens);
+                    continue;
+                }
+                c => {
+                    word.push(c);
+                    continue;
+                }
+            };
+            push_word(&mut word, &mut tokens);
+            tokens.push(Tok {
+                token,
+                line: line_no,
+            });
+        }
+        push_word(&mut word, &mut tokens);
+        tokens.push(Tok {
+            token: Token::Separator,
+            line: line_no,
+        });
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [Tok],
+    pos: usize,
+    env: &'a ScriptEnv,
+    last_line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos)
+    }
+    fn line(&self) -> usize {
+        self.peek().map(|t| t.line).unwrap_or(self.last_line)
+    }
+    fn error_at(&self, line: usize, message: &str) -> ParseError {
+        ParseError {
+            line,
+            message: message.to_owned(),
+        }
+    }
+    fn error(&self, message: &str) -> ParseError {
+        self.error_at(self.line(), message)
+    }
+    fn skip_separators(&mut self) {
+        while self.peek().is_some_and(|t| t.token == Token::Separator) {
+            self.pos += 1;
+        }
+    }
+    fn word(&mut self, what: &str) -> Result<(String, usize), ParseError> {
+        match self.peek() {
+            Some(Tok {
+                token: Token::Word(word),
+                line,
+            }) => {
+                let out = (word.clone(), *line);
+                self.pos += 1;
+                Ok(out)
+            }
+            _ => Err(self.error(&format!("expected {what}"))),
+        }
+    }
+    fn at_open(&self) -> bool {
+        self.peek().is_some_and(|t| t.token == Token::Open)
+    }
+    // statements until a closing brace or the end of the script
+    fn body(&mut self) -> Result<Vec<Box<dyn PathSegment>>, ParseError> {
+        let mut segments = Vec::new();
+        loop {
+            self.skip_separators();
+            match self.peek() {
+                None => break,
+                Some(t) if t.token == Token::Close => break,
+                _ => {}
+            }
+            segments.push(self.statement()?);
+            match self.peek().map(|t| &t.token) {
+                None | Some(Token::Separator) | Some(Token::Close) => {}
+                _ => return Err(self.error("expected end of statement")),
+            }
+        }
+        Ok(segments)
+    }
+    fn block(&mut self) -> Result<Path, ParseError> {
+        if !self.at_open() {
+            return Err(self.error("expected `{`"));
+        }
+        let open_line = self.line();
+        self.pos += 1;
+        let segments = self.body()?;
+        match self.peek() {
+            Some(t) if t.token == Token::Close => self.pos += 1,
+            _ => return Err(self.error_at(open_line, "unclosed `{`")),
+        }
+        Ok(Path::new(segments))
+    }
+    fn number(&mut self, what: &str) -> Result<f64, ParseError> {
+        let (word, line) = self.word(what)?;
+        word.parse()
+            .map_err(|_| self.error_at(line, &format!("expected {what}, found `{word}`")))
+    }
+    fn count(&mut self) -> Result<usize, ParseError> {
+        let (word, line) = self.word("a count")?;
+        word.parse()
+            .map_err(|_| self.error_at(line, &format!("expected a count, found `{word}`")))
+    }
+    fn angle(&mut self) -> Result<f64, ParseError> {
+        let (word, line) = self.word("an angle")?;
+        let err = || {
+            self.error_at(
+                line,
+                &format!("expected an angle like 90deg, found `{word}`"),
+            )
+        };
+        if let Some(v) = word.strip_suffix("deg") {
+            v.parse::<f64>().map(f64::to_radians).map_err(|_| err())
+        } else if let Some(v) = word.strip_suffix("rad") {
+            v.parse().map_err(|_| err())
+        } else {
+            Err(err())
+        }
+    }
+    fn duration(&mut self) -> Result<Duration, ParseError> {
+        let (word, line) = self.word("a duration")?;
+        let err = || {
+            self.error_at(
+                line,
+                &format!("expected a duration like 500ms, found `{word}`"),
+            )
+        };
+        let secs = if let Some(v) = word.strip_suffix("ms") {
+            v.parse::<f64>().map_err(|_| err())? / 1000.0
+        } else if let Some(v) = word.strip_suffix('s') {
+            v.parse::<f64>().map_err(|_| err())?
+        } else {
+            return Err(err());
+        };
+        Duration::try_from_secs_f64(secs).map_err(|_| err())
+    }
+    fn motor_control(&mut self) -> Result<MotorControl, ParseError> {
+        let (word, line) = self.word("a voltage or brake mode")?;
+        Ok(match word.as_str() {
+            "coast" => MotorControl::BrakeCoast,
+            "brake" => MotorControl::BrakeBrake,
+            "hold" => MotorControl::BrakeHold,
+            v => {
+                let volts = v
+                    .strip_suffix('V')
+                    .and_then(|v| v.parse::<f64>().ok())
+                    .ok_or_else(|| {
+                        self.error_at(line, &format!("expected a voltage like -12V, found `{v}`"))
+                    })?;
+                MotorControl::Voltage(volts)
+            }
+        })
+    }
+    fn statement(&mut self) -> Result<Box<dyn PathSegment>, ParseError> {
+        let (command, line) = self.word("a command")?;
+        Ok(match command.as_str() {
+            "turn_to" => Box::new(TurnTo::new(self.angle()?)),
+            "ram" => {
+                let pow = self.number("a power")?;
+                Box::new(Ram::new(pow, self.duration()?))
+            }
+            "power_side" => {
+                let turn = self.number("a turn amount")?;
+                let neg = match self.peek() {
+                    Some(Tok {
+                        token: Token::Word(word),
+                        ..
+                    }) if word == "reversed" => {
+                        self.pos += 1;
+                        true
+                    }
+                    _ => false,
+                };
+                Box::new(PowerSide::new(turn, neg))
+            }
+            "wait" => Box::new(TimedSegment::new(Box::new(Nop {}), self.duration()?)),
+            "latch" => {
+                let (name, name_line) = self.word("a latch name")?;
+                let latch =
+                    self.env.latches.get(&name).ok_or_else(|| {
+                        self.error_at(name_line, &format!("no latch named `{name}`"))
+                    })?;
+                let (action, action_line) = self.word("grab or release")?;
+                let release = match action.as_str() {
+                    "grab" => false,
+                    "release" => true,
+                    _ => {
+                        return Err(self.error_at(
+                            action_line,
+                            &format!("expected grab or release, found `{action}`"),
+                        ))
+                    }
+                };
+                Box::new(LatchAction::new(latch.clone(), release))
+            }
+            "power" => {
+                let (names, names_line) = self.word("motor names")?;
+                let motors = names
+                    .split(',')
+                    .map(|name| {
+                        self.env.motors.get(name).cloned().ok_or_else(|| {
+                            self.error_at(names_line, &format!("no motor named `{name}`"))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Box::new(PowerMotors::new(motors, self.motor_control()?))
+            }
+            "driver" => Box::new(SwitchController::new()),
+            "timed" => {
+                let dur = self.duration()?;
+                Box::new(TimedSegment::new(Box::new(self.block()?), dur))
+            }
+            "repeat" => {
+                let count = self.count()?;
+                if count == 0 {
+                    return Err(self.error_at(line, "repeat count must be at least 1"));
+                }
+                // RepeatSegment counts repeats after the first run
+                Box::new(RepeatSegment::new(Box::new(self.block()?), count - 1))
+            }
+            "while" => {
+                let main = self.block()?;
+                Box::new(WhileSegment::new(main, self.block()?, true))
+            }
+            "parallel" => {
+                let (mode, mode_line) = self.word("race, all or deadline")?;
+                let end = match mode.as_str() {
+                    "race" => ParallelEnd::Race,
+                    "all" => ParallelEnd::All,
+                    "deadline" => ParallelEnd::Deadline(0),
+                    _ => {
+                        return Err(self.error_at(
+                            mode_line,
+                            &format!("expected race, all or deadline, found `{mode}`"),
+                        ))
+                    }
+                };
+                let mut children = vec![self.block()?];
+                while self.at_open() {
+                    children.push(self.block()?);
+                }
+                Box::new(ParallelSegment::new(children, end))
+            }
+            "ramsete" => {
+                let controller = self.env.ramsete.clone().ok_or_else(|| {
+                    self.error_at(line, "no ramsete controller available to scripts")
+                })?;
+                Box::new(RamsetePath::new(self.waypoints()?, controller))
+            }
+            _ => return Err(self.error_at(line, &format!("unknown command `{command}`"))),
+        })
+    }
+    // `{ x y heading; ... }` with x and y in mm
+    fn waypoints(&mut self) -> Result<Vec<(Vec2, f64)>, ParseError> {
+        if !self.at_open() {
+            return Err(self.error("expected `{`"));
+        }
+        let open_line = self.line();
+        self.pos += 1;
+        let mut waypoints = Vec::new();
+        loop {
+            self.skip_separators();
+            match self.peek() {
+                None => return Err(self.error_at(open_line, "unclosed `{`")),
+                Some(t) if t.token == Token::Close => {
+                    self.pos += 1;
+                    return Ok(waypoints);
+                }
+                _ => {}
+            }
+            let x = self.number("an x coordinate")?;
+            let y = self.number("a y coordinate")?;
+            waypoints.push((Vec2::new(x, y), self.angle()?));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::PortRegistry;
+
+    fn env() -> ScriptEnv {
+        let ports = PortRegistry::new();
+        ScriptEnv::new()
+            .latch(
+                "back",
+                Latch::new_air(ports.claim_adi(7, "back").unwrap(), false),
+            )
+            .motor("intake", ports.claim_smart(5, "intake").unwrap())
+            .motor("conveyor", ports.claim_smart(6, "conveyor").unwrap())
+            .ramsete(Ramsete::new(2.0, 0.7))
+    }
+
+    fn error(src: &str) -> ParseError {
+        parse(src, &env()).expect_err("script should not parse")
+    }
+
+    #[test]
+    fn parses_every_command() {
+        let src = "
+            turn_to 135deg
+            ram -0.2 1500ms
+            power_side 96 reversed
+            latch back release; latch back grab
+            power intake,conveyor -12V   # comment
+            power intake coast
+            wait 1s
+            timed 3000ms { power conveyor -12V }
+            repeat 2 { ram 0.5 500ms; ram -0.5 500ms }
+            while { ram 0.5 560ms } { power intake -5V }
+            parallel deadline { turn_to 1.5rad } { power intake -12V } { wait 2s }
+            ramsete { 0 10 90deg; 0 500 90deg }
+            driver
+        ";
+        let path = parse(src, &env()).unwrap();
+        assert!(!path.ended());
+        assert!(parse("", &env()).unwrap().ended());
+    }
+
+    #[test]
+    fn unknown_command() {
+        let e = error("wait 1s\n\n  spin 3\n");
+        assert_eq!(e.line, 3);
+        assert_eq!(e.message, "unknown command `spin`");
+    }
+
+    #[test]
+    fn unknown_names() {
+        let e = error("wait 1s\nlatch front grab");
+        assert_eq!((e.line, e.message.as_str()), (2, "no latch named `front`"));
+        let e = error("power intake,lift 12V");
+        assert_eq!((e.line, e.message.as_str()), (1, "no motor named `lift`"));
+        let e = error("latch back open");
+        assert_eq!(e.line, 1);
+        assert!(e.message.contains("found `open`"), "{e}");
+    }
+
+    #[test]
+    fn missing_units() {
+        for (src, expected) in [
+            ("turn_to 90", "expected an angle like 90deg, found `90`"),
+            ("ram 0.5 500", "expected a duration like 500ms, found `500`"),
+            ("wait -1s", "expected a duration like 500ms, found `-1s`"),
+            (
+                "power intake 12",
+                "expected a voltage like -12V, found `12`",
+            ),
+            ("ram fast 1s", "expected a power, found `fast`"),
+        ] {
+            let e = error(&format!("\n{src}"));
+            assert_eq!((e.line, e.message.as_str()), (2, expected), "{src}");
+        }
+    }
+
+    #[test]
+    fn unclosed_block_reports_where_it_opened() {
+        let e = error("wait 1s\ntimed 1s {\n  wait 1s\n\nwait 2s\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "unclosed `{`"));
+        let e = error("ramsete {\n 0 10 90deg\n");
+        assert_eq!((e.line, e.message.as_str()), (1, "unclosed `{`"));
+    }
+
+    #[test]
+    fn bad_structure() {
+        let e = error("wait 1s\n}\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "unexpected `}`"));
+        let e = error("wait 1s 2s");
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (1, "expected end of statement")
+        );
+        let e = error("timed 1s\nwait 1s");
+        assert_eq!((e.line, e.message.as_str()), (1, "expected `{`"));
+        let e = error("repeat 0 { wait 1s }");
+        assert_eq!(e.message, "repeat count must be at least 1");
+        let e = error("ram 0.5");
+        assert_eq!((e.line, e.message.as_str()), (1, "expected a duration"));
+    }
+
+    #[test]
+    fn ramsete_needs_a_controller() {
+        let e = parse("\nramsete { 0 10 90deg }", &ScriptEnv::new()).unwrap_err();
+        assert_eq!(e.line, 2);
+        assert_eq!(e.message, "no ramsete controller available to scripts");
+    }
+}
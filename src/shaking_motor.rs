@@ -1,7 +1,9 @@
+use crate::clock;
 use crate::path::PathSegment;
-use crate::ToBrain;
+use crate::ports::SmartPortHandle;
 use robot_serial::protocol::EncoderState;
 use robot_serial::protocol::MotorControl;
+use robot_serial::protocol::ToBrain;
 use robot_serial::protocol::ToRobot;
 use std::time::{Duration, Instant};
 
@@ -9,7 +11,7 @@ use std::time::{Duration, Instant};
 pub struct ShakingMotor {
     update_time: Instant,
     update_interval: Duration,
-    motor: usize,
+    motor: SmartPortHandle,
     radians_buffer: [f64; 2],
     stuck_threshold: f64,
     power_buffer: [f64; 2],
@@ -21,34 +23,35 @@ pub struct ShakingMotor {
 
 impl ShakingMotor {
     pub fn new(
-        motor: usize,
+        motor: SmartPortHandle,
         update_interval: Duration,
         stuck_threshold: f64,
         shaking_interval: Duration,
     ) -> Self {
         Self {
-            update_time: Instant::now(),
+            update_time: clock::now(),
             update_interval,
             motor,
             radians_buffer: [0.0, 0.0],
             power_buffer: [0.0, 0.0],
             stuck_threshold,
             shaking_interval,
-            shaking_time: Instant::now(),
+            shaking_time: clock::now(),
             shaking_dir: false,
             power: 0.0,
         }
     }
 
     pub fn update(&mut self, pkt: &ToRobot) {
-        if self.update_time.elapsed() >= self.update_interval {
-            let EncoderState::Radians(radians) = pkt.encoder_state[self.motor - 1] else {
-                log::info!("No encoder state for smart motor {}", self.motor);
+        if clock::elapsed(self.update_time) >= self.update_interval {
+            let EncoderState::Radians(radians) = pkt.encoder_state[self.motor.port().index()]
+            else {
+                log::info!("No encoder state for smart motor {:?}", self.motor);
                 return;
             };
             self.radians_buffer[1] = radians;
             self.radians_buffer.swap(0, 1);
-            self.update_time = Instant::now();
+            self.update_time = clock::now();
         }
     }
 
@@ -58,18 +61,21 @@ impl ShakingMotor {
     }
 
     pub fn write_powers(&mut self, pkt: &mut ToBrain) {
-        if self.shaking_time.elapsed() < self.shaking_interval {
+        if clock::elapsed(self.shaking_time) < self.shaking_interval {
             // the motor is shaking, don't write any power
-            pkt.set_motors[self.motor - 1] =
-                MotorControl::Voltage(if self.shaking_dir { -12.0 } else { 12.0 });
+            self.motor.set_motor(
+                pkt,
+                MotorControl::Voltage(if self.shaking_dir { -12.0 } else { 12.0 }),
+            );
 
             self.shaking_dir = !self.shaking_dir;
         } else {
             // the motor is not shaking, write the power and detect if it's stuck
             if self.is_stuck() {
-                self.shaking_time = Instant::now();
+                self.shaking_time = clock::now();
             } else {
-                pkt.set_motors[self.motor - 1] = MotorControl::Voltage(self.power * 12.0);
+                self.motor
+                    .set_motor(pkt, MotorControl::Voltage(self.power * 12.0));
             }
         }
     }
@@ -94,10 +100,10 @@ impl PathSegment for ShakingMotor {
     ) -> crate::path::PathOutput {
         self.update(odom.last_pkt().unwrap());
         self.write_powers(pkt);
-        crate::path::PathOutput::Voltages(crate::vec::Vec2::ZERO)
+        crate::path::PathOutput::Idle
     }
     fn abrupt_end(&mut self, _odom: &crate::odometry::Odom, pkt: &mut ToBrain) {
-        pkt.set_motors[self.motor - 1] = MotorControl::BrakeCoast;
+        self.motor.set_motor(pkt, MotorControl::BrakeCoast);
     }
     fn end_follow<'a>(
         &mut self,
@@ -106,4 +112,18 @@ impl PathSegment for ShakingMotor {
     ) -> Option<Vec<Box<dyn PathSegment + 'a>>> {
         None
     }
+    fn shift_time(&mut self, by: Duration) {
+        self.update_time += by;
+        self.shaking_time += by;
+    }
+    fn boxed_clone<'a>(&self) -> Box<dyn PathSegment + 'a> {
+        let mut motor = Self::new(
+            self.motor.clone(),
+            self.update_interval,
+            self.stuck_threshold,
+            self.shaking_interval,
+        );
+        motor.set_power(self.power);
+        Box::new(motor)
+    }
 }
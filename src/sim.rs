@@ -0,0 +1,358 @@
+use std::{f64::consts::TAU, time::Duration};
+
+use robot_serial::protocol::{CompState, EncoderState, ImuState, MotorControl, ToBrain, ToRobot};
+
+use crate::{
+    brain::BrainIo,
+    clock::{self, ManualClock},
+    config::{ConfigError, RobotConfig},
+    odometry::Odom,
+    path::{Path, PathOutput},
+    ports::PortRegistry,
+    vec::Vec2,
+};
+
+/// Physical description of a differential drivetrain. Lengths use the same
+/// millimetre units as `Drivebase` so the two can be built from the same
+/// numbers.
+#[derive(Debug, Clone)]
+pub struct DriveSimConfig {
+    pub left: Vec<(usize, bool)>,
+    pub right: Vec<(usize, bool)>,
+    pub imu_port: usize,
+    /// mm travelled per radian of the encoder (see `Drivebase::new`)
+    pub radians_to_mil: f64,
+    /// see `Drivebase::radius`
+    pub radius: f64,
+    /// unloaded motor speed at 12V in rpm (after gearing)
+    pub free_rpm: f64,
+    /// force at the wheel contact patch of a single stalled motor at 12V in N
+    pub stall_force: f64,
+    /// robot mass in kg
+    pub mass: f64,
+    /// moment of inertia about the turning centre in kg m^2
+    pub inertia: f64,
+    /// constant deceleration opposing linear motion in m/s^2
+    pub linear_friction: f64,
+    /// constant deceleration opposing rotation in rad/s^2
+    pub angular_friction: f64,
+    /// proportional gain (V per rpm of error) of the motors' velocity mode
+    pub velocity_kp: f64,
+    /// time advanced by every packet read
+    pub dt: Duration,
+}
+
+impl DriveSimConfig {
+    /// The default physics with the wiring and geometry of `config`.
+    pub fn for_robot(config: &RobotConfig) -> Self {
+        let drivebase = &config.drivebase;
+        Self {
+            left: drivebase.left.clone(),
+            right: drivebase.right.clone(),
+            imu_port: config.imu.port,
+            radians_to_mil: drivebase.radians_to_mil,
+            radius: drivebase.radius,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for DriveSimConfig {
+    fn default() -> Self {
+        // roughly the small robot: 3 x 600rpm motors per side on 3.25" wheels
+        Self {
+            left: vec![(1, true), (2, true), (3, false)],
+            right: vec![(8, true), (9, false), (10, false)],
+            imu_port: 15,
+            radians_to_mil: 75.0,
+            radius: 150.0,
+            free_rpm: 450.0,
+            stall_force: 40.0,
+            mass: 6.0,
+            inertia: 0.25,
+            linear_friction: 0.8,
+            angular_friction: 2.0,
+            velocity_kp: 0.05,
+            dt: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Simulated drivetrain that consumes the motor commands written by
+/// `Drivebase` and produces encoder and IMU readings.
+///
+/// `ImuState` readings are written into the IMU slot of a template packet,
+/// so the template has to already hold an `ImuState::State` for `imu_port`
+/// (for example a packet captured from the real brain). Every other field of
+/// the template, such as `comp_state` or the controller, is passed through
+/// unchanged and can be edited with `template_mut`.
+#[derive(Debug)]
+pub struct DriveSim {
+    config: DriveSimConfig,
+    template: ToRobot,
+    commands: [MotorControl; 2],
+    pos: Vec2,
+    heading: f64,
+    linear_vel: f64,
+    angular_vel: f64,
+    side_distances: Vec2,
+    elapsed: Duration,
+    warned_imu: bool,
+    clock: Option<ManualClock>,
+}
+
+impl DriveSim {
+    pub fn new(config: DriveSimConfig, template: ToRobot) -> Self {
+        assert!(!config.left.is_empty() && !config.right.is_empty());
+        Self {
+            config,
+            template,
+            commands: [MotorControl::BrakeCoast; 2],
+            pos: Vec2::ZERO,
+            heading: 0.0,
+            linear_vel: 0.0,
+            angular_vel: 0.0,
+            side_distances: Vec2::ZERO,
+            elapsed: Duration::ZERO,
+            warned_imu: false,
+            clock: None,
+        }
+    }
+    /// Advances `clock` alongside the simulation. With the same clock
+    /// installed through `clock::set_clock` the control code sees simulated
+    /// time and can run faster than real time.
+    pub fn with_clock(mut self, clock: ManualClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+    pub fn template_mut(&mut self) -> &mut ToRobot {
+        &mut self.template
+    }
+    /// Ground truth position in mm relative to where the simulation started.
+    pub fn pos(&self) -> Vec2 {
+        self.pos
+    }
+    /// Ground truth heading in radians, counterclockwise positive.
+    pub fn heading(&self) -> f64 {
+        self.heading
+    }
+    pub fn linear_velocity(&self) -> f64 {
+        self.linear_vel
+    }
+    pub fn angular_velocity(&self) -> f64 {
+        self.angular_vel
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    fn free_speed(&self) -> f64 {
+        // mm/s at the wheel
+        self.config.free_rpm / 60.0 * TAU * self.config.radians_to_mil
+    }
+    // Voltages written by `Drivebase` are negated for reversed motors so the
+    // side command is read from the first motor and corrected for that.
+    fn read_side(pkt: &ToBrain, motors: &[(usize, bool)]) -> MotorControl {
+        let (port, rev) = motors[0];
+        match pkt.set_motors[port - 1] {
+            MotorControl::Voltage(v) if rev => MotorControl::Voltage(-v),
+            MotorControl::Velocity(v) if rev => MotorControl::Velocity(-v),
+            other => other,
+        }
+    }
+    // returns the force in N pushing a side forward given its speed in mm/s
+    fn side_force(&self, command: MotorControl, motors: usize, side_vel: f64) -> f64 {
+        let free_speed = self.free_speed();
+        let velocity_mode = |target: f64| {
+            let feedforward = 12.0 * target / free_speed;
+            let error_rpm = (target - side_vel) / self.config.radians_to_mil / TAU * 60.0;
+            feedforward + self.config.velocity_kp * error_rpm
+        };
+        let voltage = match command {
+            MotorControl::Voltage(v) => v,
+            MotorControl::Velocity(rpm) => {
+                velocity_mode(rpm as f64 / 60.0 * TAU * self.config.radians_to_mil)
+            }
+            MotorControl::BrakeHold => velocity_mode(0.0),
+            // coast disconnects the motor so there is no back-emf braking
+            MotorControl::BrakeCoast => return 0.0,
+            // brake shorts the motor leads which is the same as 0V
+            _ => 0.0,
+        }
+        .clamp(-12.0, 12.0);
+        motors as f64 * self.config.stall_force * (voltage / 12.0 - side_vel / free_speed)
+    }
+    /// Advances the simulation by `dt`.
+    pub fn step(&mut self, dt: Duration) {
+        let dt_s = dt.as_secs_f64();
+        let r = self.config.radius;
+        let left_vel = self.linear_vel - self.angular_vel * r;
+        let right_vel = self.linear_vel + self.angular_vel * r;
+
+        let fl = self.side_force(self.commands[0], self.config.left.len(), left_vel);
+        let fr = self.side_force(self.commands[1], self.config.right.len(), right_vel);
+
+        // m/s^2 converted to mm/s^2
+        let linear_acc = (fl + fr) / self.config.mass * 1000.0;
+        // torque in N m with the radius converted from mm
+        let angular_acc = (fr - fl) * r / 1000.0 / self.config.inertia;
+
+        self.linear_vel = apply_friction(
+            self.linear_vel + linear_acc * dt_s,
+            self.config.linear_friction * 1000.0 * dt_s,
+        );
+        self.angular_vel = apply_friction(
+            self.angular_vel + angular_acc * dt_s,
+            self.config.angular_friction * dt_s,
+        );
+
+        let dtheta = self.angular_vel * dt_s;
+        let average_heading = self.heading + 0.5 * dtheta;
+        let (sin, cos) = average_heading.sin_cos();
+        self.pos = self.pos + Vec2::new(cos, sin) * (self.linear_vel * dt_s);
+        self.heading += dtheta;
+
+        self.side_distances = self.side_distances
+            + Vec2::new(
+                (self.linear_vel - self.angular_vel * r) * dt_s,
+                (self.linear_vel + self.angular_vel * r) * dt_s,
+            );
+        self.elapsed += dt;
+        if let Some(clock) = &self.clock {
+            clock.advance(dt);
+        }
+    }
+    /// Builds the packet the brain would report for the current state.
+    pub fn packet(&mut self) -> ToRobot {
+        let mut pkt = self.template.clone();
+        let write_side = |pkt: &mut ToRobot, motors: &[(usize, bool)], distance: f64| {
+            for (port, rev) in motors {
+                let radians = distance / self.config.radians_to_mil;
+                pkt.encoder_state[port - 1] =
+                    EncoderState::Radians(if *rev { -radians } else { radians });
+            }
+        };
+        write_side(&mut pkt, &self.config.left, self.side_distances.x);
+        write_side(&mut pkt, &self.config.right, self.side_distances.y);
+
+        // `Imu` negates z_rotation so it has to be stored clockwise positive
+        if let ImuState::State { z_rotation, .. } = &mut pkt.imu_state[self.config.imu_port - 1] {
+            *z_rotation = -self.heading.to_degrees();
+        } else if !self.warned_imu {
+            log::warn!(
+                "simulation template has no imu state on port {}",
+                self.config.imu_port
+            );
+            self.warned_imu = true;
+        }
+        pkt
+    }
+}
+
+fn apply_friction(vel: f64, dv: f64) -> f64 {
+    if vel.abs() <= dv {
+        0.0
+    } else {
+        vel - dv * vel.signum()
+    }
+}
+
+impl BrainIo for DriveSim {
+    fn read_packet(&mut self) -> Result<ToRobot, robot_serial::Error> {
+        self.step(self.config.dt);
+        Ok(self.packet())
+    }
+    fn write_packet(&mut self, pkt: &ToBrain) -> Result<(), robot_serial::Error> {
+        self.commands = [
+            Self::read_side(pkt, &self.config.left),
+            Self::read_side(pkt, &self.config.right),
+        ];
+        Ok(())
+    }
+}
+
+/// Follows `path` for `ticks` packets the way the auton loop does, against a
+/// fresh simulation of the robot in `config` starting at the origin. Time
+/// comes from a `ManualClock` that is removed again afterwards, so a run
+/// doesn't depend on how fast the machine is.
+///
+/// Returns the packet written on every tick so runs can be compared.
+pub fn run_path<const N: usize>(
+    config: &RobotConfig,
+    path: &mut Path,
+    ticks: usize,
+) -> Result<Vec<ToBrain>, ConfigError> {
+    let sim_config = DriveSimConfig::for_robot(config);
+    let mut template = ToRobot::default();
+    template.imu_state[sim_config.imu_port - 1] = ImuState::State {
+        x_rotation: 0.0,
+        y_rotation: 0.0,
+        z_rotation: 0.0,
+    };
+    template.comp_state = CompState::Auton(0);
+
+    let ports = PortRegistry::new();
+    let mut drivebase = config.drivebase::<N>(&ports)?;
+    let mut imu = config.imu(&ports)?;
+    let mut angle_pid = config.angle_pid();
+
+    let clock = ManualClock::new();
+    clock::set_clock(clock.clone());
+    let mut sim = DriveSim::new(sim_config, template).with_clock(clock);
+    let pkt = sim.packet();
+    imu.update(&pkt);
+    drivebase.update(&pkt);
+    let mut odom = Odom::new(Vec2::ZERO, 0.0, &imu, &drivebase);
+
+    // like `Brain` the packet carries over between ticks
+    let mut to_brain = ToBrain::default();
+    let mut written = Vec::with_capacity(ticks);
+    for _ in 0..ticks {
+        sim.step(sim.config.dt);
+        let pkt = sim.packet();
+        imu.update(&pkt);
+        drivebase.update(&pkt);
+        odom.update(&imu, &drivebase, &pkt);
+
+        if !path.is_paused() {
+            match path.follow(&odom, &mut angle_pid, &mut to_brain) {
+                PathOutput::Voltages(v) => drivebase.write_voltage(v.x, v.y, &mut to_brain),
+                PathOutput::Idle => drivebase.write_voltage(0.0, 0.0, &mut to_brain),
+                PathOutput::LinearAngularVelocity(v) => {
+                    drivebase.write_linear_angular_vel(v.x, v.y, &mut to_brain)
+                }
+                PathOutput::WheelVelocities(v) => {
+                    drivebase.write_wheel_velocities(v.x, v.y, &mut to_brain)
+                }
+                PathOutput::SwitchToDriver => path.pause(),
+            }
+        }
+        let _ = sim.write_packet(&to_brain);
+        written.push(to_brain.clone());
+    }
+    clock::reset_clock();
+    Ok(written)
+}
+
+/// Groups `packets` into runs of the same packet and how many ticks each
+/// was written for, so the output of `run_path` can be compared. Runs of
+/// `skip` ticks or fewer are dropped to allow for paths that take a tick or
+/// two longer to hand over. Packets are compared by their debug output as
+/// the protocol types aren't guaranteed to be comparable.
+pub fn packet_runs(packets: &[ToBrain], skip: usize) -> Vec<(String, usize)> {
+    let mut runs: Vec<(String, usize)> = Vec::new();
+    for pkt in packets {
+        let pkt = format!("{pkt:?}");
+        match runs.last_mut() {
+            Some((last, ticks)) if *last == pkt => *ticks += 1,
+            _ => runs.push((pkt, 1)),
+        }
+    }
+    let mut merged: Vec<(String, usize)> = Vec::new();
+    for (pkt, ticks) in runs.into_iter().filter(|v| v.1 > skip) {
+        match merged.last_mut() {
+            Some((last, last_ticks)) if *last == pkt => *last_ticks += ticks,
+            _ => merged.push((pkt, ticks)),
+        }
+    }
+    merged
+}
@@ -4,49 +4,86 @@ use std::{
 };
 
 use communication::RobotInfo;
-use imu::Imu;
-use latch::LatchAction;
-use modifier_path::{Nop, TimedSegment, WhileSegment};
-use path::{PowerMotors, Ram, SwitchController, TurnTo};
+use firecracker::{
+    auton::{AutonRegistry, AutonSelector},
+    brain, clock,
+    config::{ConfigError, RobotConfig},
+    latch::{Latch, LatchAction},
+    modifier_path::{Nop, TimedSegment, WhileSegment},
+    odometry, path,
+    path::{Path, PowerMotors, Ram, SwitchController, TurnTo},
+    ports::{PortRegistry, SmartPortHandle},
+    record,
+    script::{self, ScriptEnv},
+    trace,
+    vec::Vec2,
+};
 use robot_serial::protocol::{controller::*, *};
-use vec::Vec2;
-
-mod brain;
-mod controller;
-mod drivebase;
-mod imu;
-mod latch;
-mod modifier_path;
-mod odometry;
-mod path;
-mod pid;
-mod ramsete;
-mod shaking_motor;
-mod vec;
 
 // cartesion coordinate space
 
-fn main() {
-    let _ =
-        communication::Logger::try_init(RobotInfo::new("small robot", 0.45, 0.45), true).unwrap();
-    let (mut brain, mut controller) = brain::Brain::init();
+const DEFAULT_CONFIG: &str = include_str!("../robots/small.toml");
 
-    let mut drivebase = drivebase::Drivebase::new(
-        [(1, true), (2, true), (3, false)],
-        [(8, true), (9, false), (10, false)],
-        MotorControl::BrakeBrake,
-        150.0,
-        75.0,
-    );
+// brain traffic is only recorded when run with --record, each log is capped
+// at this many bytes
+const RECORD_LIMIT: u64 = 64 * 1024 * 1024;
+
+// everything main claims from the config
+const LATCHES: [&str; 2] = ["front", "back"];
+const MOTORS: [&str; 2] = ["intake", "conveyor"];
+
+fn check_config(config: Result<RobotConfig, ConfigError>) -> Result<RobotConfig, ConfigError> {
+    let config = config?;
+    config.require::<3>(&LATCHES, &MOTORS)?;
+    Ok(config)
+}
+
+// the config is read from the path given as the first argument (or
+// small.toml), falling back to the config the binary was built with
+fn load_config() -> RobotConfig {
+    let path = std::env::args()
+        .skip(1)
+        .find(|v| !v.starts_with("--"))
+        .unwrap_or_else(|| "small.toml".to_owned());
+    match check_config(RobotConfig::load(&path)) {
+        Ok(config) => {
+            log::info!("Loaded robot config from {path}");
+            config
+        }
+        Err(e) => {
+            log::warn!("Using built in robot config, {path}: {e}");
+            check_config(RobotConfig::parse(DEFAULT_CONFIG))
+                .expect("built in robot config is invalid")
+        }
+    }
+}
 
-    let mut front_latch = latch::Latch::new_air(8, false);
-    let mut back_latch = latch::Latch::new_air(7, false);
-    let front_latch_release = LatchAction::new(front_latch.clone(), true);
-    let front_latch_attach = LatchAction::new(front_latch.clone(), false);
-    let back_latch_release = LatchAction::new(back_latch.clone(), true);
-    let back_latch_attach = LatchAction::new(back_latch.clone(), false);
+#[derive(Clone)]
+struct Mechanisms {
+    intake: SmartPortHandle,
+    conveyor: SmartPortHandle,
+    front_latch: Latch,
+    back_latch: Latch,
+}
 
-    let wait_n = |v: Duration| TimedSegment::new(Box::new(Nop {}), v);
+struct Stages {
+    init_front_latch: Path,
+    stage_one: WhileSegment,
+    stage_two: WhileSegment,
+    option_b: Path,
+    option_c: Path,
+}
+
+fn wait_n(v: Duration) -> TimedSegment {
+    TimedSegment::new(Box::new(Nop {}), v)
+}
+
+// builds a fresh copy of every auton stage
+fn stages(m: &Mechanisms) -> Stages {
+    let front_latch_release = LatchAction::new(m.front_latch.clone(), true);
+    let front_latch_attach = LatchAction::new(m.front_latch.clone(), false);
+    let back_latch_release = LatchAction::new(m.back_latch.clone(), true);
+    let back_latch_attach = LatchAction::new(m.back_latch.clone(), false);
 
     let init_front_latch = path!(
         front_latch_attach.clone(),
@@ -69,14 +106,14 @@ fn main() {
         back_latch_attach.clone(),
         // score 2x ringsTimedSegment::new(
         TimedSegment::new(
-            Box::new(PowerMotors::new(vec![6], MotorControl::Voltage(-12.0))),
+            Box::new(PowerMotors::new(vec![m.conveyor.clone()], MotorControl::Voltage(-12.0))),
             Duration::from_millis(3000),
         ),
     );
 
     let stage_one = WhileSegment::new(
         path!(get_first_ring, score_two_rings),
-        path!(PowerMotors::new(vec![5], MotorControl::Voltage(-5.0))),
+        path!(PowerMotors::new(vec![m.intake.clone()], MotorControl::Voltage(-5.0))),
         true,
     );
 
@@ -94,14 +131,17 @@ fn main() {
             get_last_ring,
             wait_n(Duration::from_secs(1))
         ),
-        path!(PowerMotors::new(vec![5], MotorControl::Voltage(-12.0))),
+        path!(PowerMotors::new(vec![m.intake.clone()], MotorControl::Voltage(-12.0))),
         true,
     );
 
     let turn_to_wall_stake = path!(TurnTo::new(5.0f64.to_radians()));
     let ram_into_wall_stake = path!(Ram::new(-0.1, Duration::from_millis(2000)));
     let score_last_ring = path!(TimedSegment::new(
-        Box::new(PowerMotors::new(vec![5, 6], MotorControl::Voltage(-12.0))),
+        Box::new(PowerMotors::new(
+            vec![m.intake.clone(), m.conveyor.clone()],
+            MotorControl::Voltage(-12.0),
+        )),
         Duration::from_millis(3000),
     ));
     let turn_to_ladder = path!(TurnTo::new(0.0));
@@ -123,7 +163,7 @@ fn main() {
         Ram::new(-0.2, Duration::from_millis(1500)),
         back_latch_attach.clone(),
         TimedSegment::new(
-            Box::new(PowerMotors::new(vec![6], MotorControl::Voltage(-12.0))),
+            Box::new(PowerMotors::new(vec![m.conveyor.clone()], MotorControl::Voltage(-12.0))),
             Duration::from_millis(3000),
         )
     );
@@ -139,73 +179,140 @@ fn main() {
         turn_to_new_point_two,
         WhileSegment::new(
             score_last_ring,
-            path!(PowerMotors::new(vec![5], MotorControl::Voltage(-12.0))),
+            path!(PowerMotors::new(vec![m.intake.clone()], MotorControl::Voltage(-12.0))),
             true,
         ),
         turn_to_ladder,
         ram_to_ladder,
     );
 
-    let mut auton_path = path!( 
-        // init the front latch
-        
-        /* 
-        init_front_latch,
-        WhileSegment::new(
-            /*path!(
-            Ram::new(0.5, Duration::from_millis(560)),*/
-            //TurnTo::new(PI),
-            path!(path::PowerSide { start: std::time::Instant::now() }),
-            // turn left to align backwards with mobile goal
-            /*back_latch_release.clone(),
-            // go backwards to mobile goal
-            Ram::new(-0.2, Duration::from_millis(3000)),
-            // latch onto goal
-            back_latch_attach.clone(),
-            // score 2x ringsTimedSegment::new(
-            PowerMotors::new(vec![6], MotorControl::Voltage(-12.0)),
-        ),*/
-        path!(PowerMotors::new(vec![5], MotorControl::Voltage(-12.0))), true),
-    )   
-
-        */
-
-        
-        // init the front latch
+    Stages {
         init_front_latch,
-        // auton stage one
-        // get first ring and score two rings
         stage_one,
-        //SwitchController {},
-        // auton stage two
-        // turn to last ring and get last ring
-        //stage_two,
-        // option b
-        // turn to wall stake, ram into wall stake
-        // score ring, turn to ladder, ram into ladder
-        //option_b,
-        // option c
-        // turn to new point, ram into new point, turn to new point 2
-        // score last ring, turn to ladder, ram into ladder
-        //option_c,
-    );
-    let mut imu = Imu::new(15);
+        stage_two,
+        option_b,
+        option_c,
+    }
+}
+
+fn autons(m: &Mechanisms) -> AutonRegistry {
+    let mut autons = AutonRegistry::new();
+    let mech = m.clone();
+    autons.add("stage one", move || {
+        let s = stages(&mech);
+        path!(
+            // init the front latch
+            s.init_front_latch,
+            // auton stage one
+            // get first ring and score two rings
+            s.stage_one,
+            //SwitchController {},
+        )
+    });
+    let mech = m.clone();
+    // turn to last ring and get last ring then
+    // turn to wall stake, ram into wall stake
+    // score ring, turn to ladder, ram into ladder
+    autons.add("option b", move || {
+        let s = stages(&mech);
+        path!(
+            s.init_front_latch,
+            s.stage_one,
+            s.stage_two,
+            s.option_b
+        )
+    });
+    let mech = m.clone();
+    // turn to last ring and get last ring then
+    // turn to new point, ram into new point, turn to new point 2
+    // score last ring, turn to ladder, ram into ladder
+    autons.add("option c", move || {
+        let s = stages(&mech);
+        path!(
+            s.init_front_latch,
+            s.stage_one,
+            s.stage_two,
+            s.option_c
+        )
+    });
+
+    // scripted autons from `autons/` next to the binary, see `script`
+    if let Err(e) = script::register_dir(&mut autons, "autons", &script_env(m)) {
+        log::warn!("Failed to load scripted autons: {e}");
+    }
+    autons
+}
+
+fn script_env(m: &Mechanisms) -> ScriptEnv {
+    ScriptEnv::new()
+        .latch("front", m.front_latch.clone())
+        .latch("back", m.back_latch.clone())
+        .motor("intake", m.intake.clone())
+        .motor("conveyor", m.conveyor.clone())
+}
+
+fn start_recording(brain: &mut brain::Brain, name: &str) {
+    match record::Recorder::create(name) {
+        Ok(recorder) => brain.record_to(recorder.limit(RECORD_LIMIT)),
+        Err(e) => log::warn!("Failed to start recording brain traffic to {name}: {e}"),
+    }
+}
+
+fn main() {
+    let _ =
+        communication::Logger::try_init(RobotInfo::new("small robot", 0.45, 0.45), true).unwrap();
+    let (mut brain, mut controller) = brain::Brain::init();
+    let run_id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let record = std::env::args().any(|v| v == "--record");
+    let mut log_part = 0;
+
+    let config = load_config();
+    let ports = PortRegistry::new();
+    let mut drivebase = config.drivebase::<3>(&ports).unwrap();
+    let intake = config.motor(&ports, "intake").unwrap();
+    let conveyor = config.motor(&ports, "conveyor").unwrap();
+
+    let mut front_latch = config.latch(&ports, "front").unwrap();
+    let mut back_latch = config.latch(&ports, "back").unwrap();
+    let mechanisms = Mechanisms {
+        intake: intake.clone(),
+        conveyor: conveyor.clone(),
+        front_latch: front_latch.clone(),
+        back_latch: back_latch.clone(),
+    };
+    let mut selector = AutonSelector::new(autons(&mechanisms), "auton.txt");
+    let mut auton_path: Option<Path> = None;
+
+    let mut imu = config.imu(&ports).unwrap();
     let mut odom = odometry::Odom::new(Vec2::ZERO, 0.0, &imu, &drivebase);
 
     // for now, the best arguments
-    let mut angle_pid = pid::Pid::new(0.5, 0.8, 0.);
+    let mut angle_pid = config.angle_pid();
 
     // init time is used to wait for the robot to settl
-    let init_time = std::time::Instant::now();
-    let mut finished = false;
+    let init_time = clock::now();
     let mut reversed = false;
+    let mut last_comp_state = None;
 
     loop {
         let (pkt, _is_updated) = brain.update_state(&mut controller);
+
+        let comp_state = std::mem::discriminant(&pkt.comp_state);
+        let comp_state_changed = last_comp_state.replace(comp_state) != Some(comp_state);
+        // a new log is started for every comp state so each one is flushed
+        // as soon as that part of the match is over, the robot is powered
+        // off rather than shut down so only the last log can lose its tail
+        if record && comp_state_changed {
+            start_recording(&mut brain, &format!("brain-{run_id}-{log_part}.log"));
+            log_part += 1;
+        }
         let pkt_to_write = brain.get_brain_pkt();
 
         // wait for the robot to settle, imu to warm up
-        if init_time.elapsed() < Duration::from_secs(2) {
+        if clock::elapsed(init_time) < Duration::from_secs(2) {
             // change nothing
             brain.write_changes();
             continue;
@@ -220,22 +327,61 @@ fn main() {
         drivebase.update(&pkt);
         odom.update(&imu, &drivebase, &pkt);
 
+        // stop whatever the auton was doing when the field changes state so
+        // nothing is left running, a later auton period starts from scratch
+        if comp_state_changed {
+            if let Some(mut path) = auton_path.take() {
+                path.cancel(&odom, pkt_to_write);
+                drivebase.write_voltage(0.0, 0.0, pkt_to_write);
+            }
+        }
+
         if let CompState::Auton(_) = pkt.comp_state {
-            if !finished {
+            let auton_path = auton_path.get_or_insert_with(|| {
+                trace::enable();
+                selector.build()
+            });
+            // the driver can take over at any point (Y) and hand back (X)
+            if auton_path.is_paused() {
+                if controller.pressed(X) {
+                    auton_path.resume(&mut angle_pid);
+                }
+            } else if controller.pressed(Y) {
+                auton_path.pause();
+            }
+            if !auton_path.is_paused() {
                 let out = auton_path.follow(&mut odom, &mut angle_pid, pkt_to_write);
                 match out {
                     path::PathOutput::Voltages(v) => {
                         drivebase.write_voltage(v.x, v.y, pkt_to_write)
                     }
                     path::PathOutput::LinearAngularVelocity(lr) => {
-                        drivebase.write_powers(lr.x, lr.y, pkt_to_write)
+                        drivebase.write_linear_angular_vel(lr.x, lr.y, pkt_to_write)
+                    }
+                    path::PathOutput::WheelVelocities(lr) => {
+                        drivebase.write_wheel_velocities(lr.x, lr.y, pkt_to_write)
                     }
-                    path::PathOutput::SwitchToDriver => finished = true,
+                    path::PathOutput::SwitchToDriver => auton_path.pause(),
+                    path::PathOutput::Idle => drivebase.write_voltage(0.0, 0.0, pkt_to_write),
                 }
             }
         }
 
-        if pkt.comp_state == CompState::Driver {
+        if !matches!(pkt.comp_state, CompState::Auton(_) | CompState::Driver) {
+            // pre-match, pick which auton to run
+            selector.update(&controller);
+        }
+
+        // save what the auton did for looking at after the match
+        if pkt.comp_state == CompState::Driver && trace::is_enabled() {
+            let trace_name = format!("trace-{run_id}.csv");
+            if let Err(e) = trace::disable().save(&trace_name) {
+                log::warn!("Failed to save auton trace to {trace_name}: {e}");
+            }
+        }
+
+        let auton_paused = auton_path.as_ref().is_some_and(Path::is_paused);
+        if pkt.comp_state == CompState::Driver || auton_paused {
             if controller.pressed(DOWN) {
                 reversed = !reversed;
             }
@@ -251,18 +397,18 @@ fn main() {
                 controller.held(LEFT_TRIGGER_1),
                 controller.held(LEFT_TRIGGER_2),
             ) {
-                (true, false) => pkt_to_write.set_motors[4] = MotorControl::Voltage(12.0),
-                (false, true) => pkt_to_write.set_motors[4] = MotorControl::Voltage(-12.0),
-                _ => pkt_to_write.set_motors[4] = MotorControl::BrakeBrake,
+                (true, false) => intake.set_motor(pkt_to_write, MotorControl::Voltage(12.0)),
+                (false, true) => intake.set_motor(pkt_to_write, MotorControl::Voltage(-12.0)),
+                _ => intake.set_motor(pkt_to_write, MotorControl::BrakeBrake),
             }
 
             match (
                 controller.held(RIGHT_TRIGGER_1),
                 controller.held(RIGHT_TRIGGER_2),
             ) {
-                (true, false) => pkt_to_write.set_motors[5] = MotorControl::Voltage(12.0),
-                (false, true) => pkt_to_write.set_motors[5] = MotorControl::Voltage(-12.0),
-                _ => pkt_to_write.set_motors[5] = MotorControl::BrakeCoast,
+                (true, false) => conveyor.set_motor(pkt_to_write, MotorControl::Voltage(12.0)),
+                (false, true) => conveyor.set_motor(pkt_to_write, MotorControl::Voltage(-12.0)),
+                _ => conveyor.set_motor(pkt_to_write, MotorControl::BrakeCoast),
             }
 
             if controller.pressed(B) {
@@ -277,6 +423,47 @@ fn main() {
         }
 
         //
+        ports.finish_tick();
         brain.write_changes();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use firecracker::sim;
+
+    #[test]
+    fn stage_one_script_matches_built_in() {
+        let config = RobotConfig::parse(DEFAULT_CONFIG).unwrap();
+        let ports = PortRegistry::new();
+        let m = Mechanisms {
+            intake: config.motor(&ports, "intake").unwrap(),
+            conveyor: config.motor(&ports, "conveyor").unwrap(),
+            front_latch: config.latch(&ports, "front").unwrap(),
+            back_latch: config.latch(&ports, "back").unwrap(),
+        };
+        let registry = autons(&m);
+        let mut built_in = registry.build(registry.position("stage one").unwrap()).unwrap();
+        let src = include_str!("../autons/stage_one.auton");
+        let mut scripted = script::parse(src, &script_env(&m)).unwrap();
+
+        // long enough for both to finish
+        let ticks = 2000;
+        let built_in_out = sim::run_path::<3>(&config, &mut built_in, ticks).unwrap();
+        let scripted_out = sim::run_path::<3>(&config, &mut scripted, ticks).unwrap();
+        assert!(built_in.ended() && scripted.ended());
+
+        // the built in auton nests paths which idle for a tick as each one
+        // hands over, so runs of the same packet are compared rather than
+        // every tick, allowing a few ticks of slack
+        const SLACK: usize = 5;
+        let built_in_runs = sim::packet_runs(&built_in_out, SLACK);
+        let scripted_runs = sim::packet_runs(&scripted_out, SLACK);
+        assert_eq!(built_in_runs.len(), scripted_runs.len());
+        for ((a, a_ticks), (b, b_ticks)) in built_in_runs.iter().zip(&scripted_runs) {
+            assert_eq!(a, b);
+            assert!(a_ticks.abs_diff(*b_ticks) <= SLACK, "{a_ticks} vs {b_ticks} ticks of {a}");
+        }
+    }
+}
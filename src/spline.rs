@@ -0,0 +1,213 @@
+//! Smooth paths through a few poses, instead of listing every waypoint.
+//!
+//! Each pair of neighbouring poses is joined by a cubic Bézier whose inner
+//! control points sit along the poses' headings, so the path leaves and
+//! arrives at every pose facing the way it says. The result is sampled at
+//! even spacing along its length, ready for `RamsetePath::new`,
+//! `PurePursuitPath::new` or `Trajectory::generate`.
+
+use crate::vec::Vec2;
+
+// lookup steps per segment when measuring length
+const LENGTH_STEPS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Spline {
+    poses: Vec<(Vec2, f64)>,
+    tension: f64,
+}
+
+/// A spot where the path turns tighter than the drivebase can follow
+/// without the inside wheels going backwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TightTurn {
+    /// mm along the path
+    pub distance: f64,
+    pub pos: Vec2,
+    /// radius of the turn here, in mm
+    pub turn_radius: f64,
+}
+
+impl std::fmt::Display for TightTurn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.0}mm radius turn at ({:.0}, {:.0}), {:.0}mm along the path",
+            self.turn_radius, self.pos.x, self.pos.y, self.distance
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    segment: usize,
+    u: f64,
+    distance: f64,
+}
+
+impl Spline {
+    pub fn new<T: IntoIterator<Item = (Vec2, f64)>>(poses: T) -> Self {
+        Self {
+            poses: poses.into_iter().collect(),
+            tension: 0.4,
+        }
+    }
+    /// How far the control points sit from each pose, as a fraction of the
+    /// distance to the next pose. 0.4 by default, higher keeps closer to
+    /// the headings for longer but turns tighter in between.
+    pub fn tension(mut self, tension: f64) -> Self {
+        self.tension = tension;
+        self
+    }
+    fn control_points(&self, segment: usize) -> [Vec2; 4] {
+        let (p0, h0) = self.poses[segment];
+        let (p3, h3) = self.poses[segment + 1];
+        let reach = (p3 - p0).mag() * self.tension;
+        let dir = |h: f64| Vec2::new(h.cos(), h.sin());
+        [p0, p0 + dir(h0) * reach, p3 - dir(h3) * reach, p3]
+    }
+    fn point(&self, segment: usize, u: f64) -> Vec2 {
+        let [a, b, c, d] = self.control_points(segment);
+        let v = 1.0 - u;
+        a * (v * v * v) + b * (3.0 * v * v * u) + c * (3.0 * v * u * u) + d * (u * u * u)
+    }
+    // first and second derivatives with respect to u
+    fn derivatives(&self, segment: usize, u: f64) -> (Vec2, Vec2) {
+        let [a, b, c, d] = self.control_points(segment);
+        let v = 1.0 - u;
+        let first = (b - a) * (3.0 * v * v) + (c - b) * (6.0 * v * u) + (d - c) * (3.0 * u * u);
+        let second = (c - b * 2.0 + a) * (6.0 * v) + (d - c * 2.0 + b) * (6.0 * u);
+        (first, second)
+    }
+    fn heading(&self, segment: usize, u: f64) -> f64 {
+        let (first, _) = self.derivatives(segment, u);
+        if first == Vec2::ZERO {
+            // only happens with a tension of 0 at the poses themselves
+            return if u < 0.5 {
+                self.poses[segment].1
+            } else {
+                self.poses[segment + 1].1
+            };
+        }
+        first.angle()
+    }
+    /// Signed curvature (1/mm, positive turning left) at a point.
+    fn curvature(&self, segment: usize, u: f64) -> f64 {
+        let (first, second) = self.derivatives(segment, u);
+        let speed = first.mag();
+        if speed == 0.0 {
+            return 0.0;
+        }
+        (first.x * second.y - first.y * second.x) / speed.powi(3)
+    }
+    // parameters roughly `spacing` mm apart along the whole path, always
+    // including the last pose
+    fn samples(&self, spacing: f64) -> Vec<Sample> {
+        assert!(spacing > 0.0, "spline spacing must be positive");
+        let mut samples = Vec::new();
+        let mut travelled = 0.0;
+        let mut next = 0.0;
+        for segment in 0..self.poses.len().saturating_sub(1) {
+            let mut last = self.point(segment, 0.0);
+            for step in 1..=LENGTH_STEPS {
+                let u = step as f64 / LENGTH_STEPS as f64;
+                let pos = self.point(segment, u);
+                let len = (pos - last).mag();
+                while travelled + len >= next && len > 0.0 {
+                    let frac = (next - travelled) / len;
+                    samples.push(Sample {
+                        segment,
+                        u: u - (1.0 - frac) / LENGTH_STEPS as f64,
+                        distance: next,
+                    });
+                    next += spacing;
+                }
+                travelled += len;
+                last = pos;
+            }
+        }
+        if let Some(segment) = self.poses.len().checked_sub(2) {
+            // the end is skipped when the length isn't a multiple of spacing
+            if !samples.last().is_some_and(|v| v.distance >= travelled) {
+                samples.push(Sample {
+                    segment,
+                    u: 1.0,
+                    distance: travelled,
+                });
+            }
+        }
+        samples
+    }
+    /// Poses roughly `spacing` mm apart along the path, facing along it.
+    pub fn sample(&self, spacing: f64) -> Vec<(Vec2, f64)> {
+        self.samples(spacing)
+            .into_iter()
+            .map(|s| (self.point(s.segment, s.u), self.heading(s.segment, s.u)))
+            .collect()
+    }
+    /// Total length in mm.
+    pub fn length(&self) -> f64 {
+        self.samples(f64::INFINITY)
+            .last()
+            .map_or(0.0, |v| v.distance)
+    }
+    /// Every sampled point where the path turns tighter than `radius` (see
+    /// `Drivebase::radius`).
+    pub fn tight_turns(&self, spacing: f64, radius: f64) -> Vec<TightTurn> {
+        self.samples(spacing)
+            .into_iter()
+            .filter_map(|s| {
+                let turn_radius = 1.0 / self.curvature(s.segment, s.u).abs();
+                (turn_radius < radius).then(|| TightTurn {
+                    distance: s.distance,
+                    pos: self.point(s.segment, s.u),
+                    turn_radius,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn samples_are_evenly_spaced_and_end_on_the_last_pose() {
+        let end = Vec2::new(1200.0, 1200.0);
+        let spline = Spline::new([
+            (Vec2::ZERO, 0.0),
+            (Vec2::new(800.0, 400.0), FRAC_PI_2 * 0.5),
+            (end, FRAC_PI_2),
+        ]);
+        let spacing = 50.0;
+        let samples = spline.sample(spacing);
+        assert_eq!(samples[0].0, Vec2::ZERO);
+        assert_eq!(samples.last().unwrap().0, end);
+
+        let gaps: Vec<f64> = samples
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0).mag())
+            .collect();
+        let (last, rest) = gaps.split_last().unwrap();
+        for gap in rest {
+            assert!((gap - spacing).abs() < 0.01 * spacing, "{gap} apart");
+        }
+        assert!(
+            *last > 0.0 && *last <= spacing * 1.01,
+            "{last} apart at the end"
+        );
+        let total: f64 = gaps.iter().sum();
+        assert!((total - spline.length()).abs() < 0.01 * spline.length());
+    }
+
+    #[test]
+    fn exact_multiple_of_spacing_has_no_extra_sample() {
+        let spline = Spline::new([(Vec2::ZERO, 0.0), (Vec2::new(500.0, 0.0), 0.0)]);
+        let samples = spline.sample(100.0);
+        assert_eq!(samples.len(), 6);
+        assert_eq!(samples.last().unwrap().0, Vec2::new(500.0, 0.0));
+    }
+}
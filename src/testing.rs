@@ -1,17 +1,7 @@
 use communication::RobotInfo;
+use firecracker::brain;
 use robot_serial::protocol::{controller::*, *};
 
-mod brain;
-mod controller;
-mod drivebase;
-mod imu;
-mod modifier_path;
-mod odometry;
-mod path;
-mod pid;
-mod ramsete;
-mod vec;
-
 // cartesion coordinate space
 
 fn main() {
@@ -0,0 +1,180 @@
+//! Structured record of every path segment that runs, for looking at what an
+//! auton actually did after the match.
+//!
+//! Tracing is per thread like the clock: call `enable` before following a
+//! path and `take` afterwards. Nested paths are traced as well, each
+//! combinator shows up as its own record around the records of its children.
+
+use std::{cell::RefCell, io::Write, time::Instant};
+
+use serde::Serialize;
+
+use crate::{clock, odometry::Odom, path::PathSegment};
+
+/// Why a segment stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EndReason {
+    /// the segment finished on its own
+    Completed,
+    /// a `TimedSegment` or the segment's own time limit ran out
+    TimedOut,
+    /// the segment reported `Outcome::Failed`
+    Failed,
+    /// the parent ended it early
+    Aborted,
+    /// control was handed back to the driver
+    SwitchToDriver,
+}
+
+/// One run of one segment. Times are in milliseconds since tracing was
+/// enabled, the end fields are empty while the segment is still running.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceRecord {
+    pub name: String,
+    pub label: Option<String>,
+    pub start_ms: f64,
+    pub end_ms: Option<f64>,
+    pub start_x: f64,
+    pub start_y: f64,
+    pub start_heading: f64,
+    pub end_x: Option<f64>,
+    pub end_y: Option<f64>,
+    pub end_heading: Option<f64>,
+    pub end_reason: Option<EndReason>,
+    pub failure: Option<String>,
+}
+
+/// Every record of a run in the order the segments started.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    pub records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    pub fn write_csv<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for record in &self.records {
+            writer.serialize(record)?;
+        }
+        writer.flush()
+    }
+    pub fn write_json<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.records)?;
+        Ok(())
+    }
+    /// Writes JSON when the path ends in `.json` and CSV otherwise.
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        if path.extension().is_some_and(|ext| ext == "json") {
+            self.write_json(file)
+        } else {
+            self.write_csv(file)
+        }
+    }
+}
+
+struct Tracer {
+    origin: Instant,
+    // id of records[0], ids keep counting up across `take`
+    first_id: usize,
+    records: Vec<TraceRecord>,
+}
+
+thread_local! {
+    static TRACER: RefCell<Option<Tracer>> = const { RefCell::new(None) };
+}
+
+/// Starts a new trace on the current thread, dropping any records that
+/// weren't taken.
+pub fn enable() {
+    TRACER.with(|t| {
+        let first_id = t
+            .borrow()
+            .as_ref()
+            .map_or(0, |t| t.first_id + t.records.len());
+        *t.borrow_mut() = Some(Tracer {
+            origin: clock::now(),
+            first_id,
+            records: Vec::new(),
+        });
+    });
+}
+
+/// Stops tracing and returns what was recorded.
+pub fn disable() -> Trace {
+    let records = TRACER.with(|t| t.borrow_mut().take().map(|t| t.records));
+    Trace {
+        records: records.unwrap_or_default(),
+    }
+}
+
+pub fn is_enabled() -> bool {
+    TRACER.with(|t| t.borrow().is_some())
+}
+
+/// Returns the records so far and keeps tracing. Segments that are still
+/// running are included without an end and won't be updated when they end.
+pub fn take() -> Trace {
+    let records = TRACER.with(|t| {
+        t.borrow_mut()
+            .as_mut()
+            .map(|t| {
+                t.first_id += t.records.len();
+                std::mem::take(&mut t.records)
+            })
+            .unwrap_or_default()
+    });
+    Trace { records }
+}
+
+/// Opens a record for `seg`, the returned id is used to close it.
+pub(crate) fn segment_started(seg: &dyn PathSegment, odom: &Odom) -> Option<usize> {
+    TRACER.with(|t| {
+        let mut t = t.borrow_mut();
+        let t = t.as_mut()?;
+        let pos = odom.pos();
+        t.records.push(TraceRecord {
+            name: seg.name().to_owned(),
+            label: seg.label().map(str::to_owned),
+            start_ms: ms_since(t.origin),
+            end_ms: None,
+            start_x: pos.x,
+            start_y: pos.y,
+            start_heading: odom.heading(),
+            end_x: None,
+            end_y: None,
+            end_heading: None,
+            end_reason: None,
+            failure: None,
+        });
+        Some(t.first_id + t.records.len() - 1)
+    })
+}
+
+pub(crate) fn segment_ended(id: usize, odom: &Odom, reason: EndReason, failure: Option<String>) {
+    TRACER.with(|t| {
+        let mut t = t.borrow_mut();
+        let Some(t) = t.as_mut() else {
+            return;
+        };
+        let end_ms = ms_since(t.origin);
+        let Some(record) = id
+            .checked_sub(t.first_id)
+            .and_then(|idx| t.records.get_mut(idx))
+        else {
+            return;
+        };
+        let pos = odom.pos();
+        record.end_ms = Some(end_ms);
+        record.end_x = Some(pos.x);
+        record.end_y = Some(pos.y);
+        record.end_heading = Some(odom.heading());
+        record.end_reason = Some(reason);
+        record.failure = failure;
+    });
+}
+
+fn ms_since(origin: Instant) -> f64 {
+    clock::elapsed(origin).as_secs_f64() * 1000.0
+}
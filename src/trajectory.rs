@@ -0,0 +1,210 @@
+//! Time-parameterised trajectories: a list of waypoints turned into states
+//! saying where the robot should be, and how fast it should be going, at
+//! each point in time.
+//!
+//! The velocity profile comes from two passes over the path. The forward
+//! pass accelerates from rest at each waypoint as hard as allowed, the
+//! backward pass does the same from the end so the robot can still stop,
+//! and each waypoint is also capped by the centripetal limit for how tight
+//! the path is there.
+//!
+//! Distances are mm, times are seconds and angles radians, as elsewhere.
+
+use crate::{path::optimise_target_heading, vec::Vec2};
+
+/// Limits the profile has to stay within.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryConfig {
+    /// mm/s
+    pub max_velocity: f64,
+    /// mm/s², used for both speeding up and slowing down
+    pub max_acceleration: f64,
+    /// mm/s², v² times curvature, limits speed through turns
+    pub max_centripetal: f64,
+}
+
+impl TrajectoryConfig {
+    pub fn new(max_velocity: f64, max_acceleration: f64) -> Self {
+        Self {
+            max_velocity,
+            max_acceleration,
+            max_centripetal: f64::INFINITY,
+        }
+    }
+    pub fn max_centripetal(mut self, max_centripetal: f64) -> Self {
+        self.max_centripetal = max_centripetal;
+        self
+    }
+}
+
+/// Where the robot should be at time `t` and how it should be moving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryState {
+    /// seconds since the start of the trajectory
+    pub t: f64,
+    pub pose: (Vec2, f64),
+    /// mm/s
+    pub velocity: f64,
+    /// rad/s, counterclockwise positive
+    pub angular_velocity: f64,
+    /// 1/mm, positive turning left
+    pub curvature: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trajectory {
+    states: Vec<TrajectoryState>,
+}
+
+impl Trajectory {
+    /// Profiles the path through `waypoints`. The headings are taken as
+    /// given, so they should follow the direction of travel as they do from
+    /// `Spline::sample`. Curvature is the change in heading per mm between
+    /// neighbouring waypoints.
+    pub fn generate(waypoints: &[(Vec2, f64)], config: TrajectoryConfig) -> Self {
+        let n = waypoints.len();
+        if n == 0 {
+            return Self::default();
+        }
+
+        // distance along the path to each waypoint
+        let mut dist = vec![0.0; n];
+        for i in 1..n {
+            dist[i] = dist[i - 1] + (waypoints[i].0 - waypoints[i - 1].0).mag();
+        }
+
+        let curvature: Vec<f64> = (0..n)
+            .map(|i| {
+                let (prev, next) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                let ds = dist[next] - dist[prev];
+                if ds == 0.0 {
+                    return 0.0;
+                }
+                let (from, to) = (waypoints[prev].1, waypoints[next].1);
+                (optimise_target_heading(from, to) - from) / ds
+            })
+            .collect();
+
+        let mut velocity: Vec<f64> = curvature
+            .iter()
+            .map(|k| {
+                let turn_limit = (config.max_centripetal / k.abs()).sqrt();
+                config.max_velocity.min(turn_limit)
+            })
+            .collect();
+        velocity[0] = 0.0;
+        velocity[n - 1] = 0.0;
+
+        // v² = u² + 2as
+        let reachable = |v: f64, ds: f64| (v * v + 2.0 * config.max_acceleration * ds).sqrt();
+        for i in 1..n {
+            velocity[i] = velocity[i].min(reachable(velocity[i - 1], dist[i] - dist[i - 1]));
+        }
+        for i in (0..n - 1).rev() {
+            velocity[i] = velocity[i].min(reachable(velocity[i + 1], dist[i + 1] - dist[i]));
+        }
+
+        let mut t = 0.0;
+        let states = (0..n)
+            .map(|i| {
+                if i > 0 {
+                    // constant acceleration between waypoints
+                    let mean = 0.5 * (velocity[i - 1] + velocity[i]);
+                    if mean > 0.0 {
+                        t += (dist[i] - dist[i - 1]) / mean;
+                    }
+                }
+                TrajectoryState {
+                    t,
+                    pose: waypoints[i],
+                    velocity: velocity[i],
+                    angular_velocity: velocity[i] * curvature[i],
+                    curvature: curvature[i],
+                }
+            })
+            .collect();
+        Self { states }
+    }
+    pub fn states(&self) -> &[TrajectoryState] {
+        &self.states
+    }
+    /// Length in seconds.
+    pub fn duration(&self) -> f64 {
+        self.states.last().map_or(0.0, |v| v.t)
+    }
+    /// The state at `t` seconds, interpolated between the generated states
+    /// and held at the ends.
+    pub fn sample(&self, t: f64) -> Option<TrajectoryState> {
+        let idx = self.states.partition_point(|v| v.t <= t);
+        let (Some(a), Some(b)) = (self.states.get(idx.saturating_sub(1)), self.states.get(idx))
+        else {
+            return self.states.last().copied();
+        };
+        if idx == 0 || b.t == a.t {
+            return Some(*a);
+        }
+        let frac = (t - a.t) / (b.t - a.t);
+        let lerp = |a: f64, b: f64| a + (b - a) * frac;
+        let heading = lerp(a.pose.1, optimise_target_heading(a.pose.1, b.pose.1));
+        Some(TrajectoryState {
+            t,
+            pose: (a.pose.0 + (b.pose.0 - a.pose.0) * frac, heading),
+            velocity: lerp(a.velocity, b.velocity),
+            angular_velocity: lerp(a.angular_velocity, b.angular_velocity),
+            curvature: lerp(a.curvature, b.curvature),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    // 600mm straight then a quarter circle of radius 500 to the left
+    fn waypoints() -> Vec<(Vec2, f64)> {
+        let straight = (0..=30).map(|i| (Vec2::new(i as f64 * 20.0, 0.0), 0.0));
+        let arc = (1..=40).map(|i| {
+            let a = i as f64 / 40.0 * FRAC_PI_2;
+            (
+                Vec2::new(600.0 + 500.0 * a.sin(), 500.0 - 500.0 * a.cos()),
+                a,
+            )
+        });
+        straight.chain(arc).collect()
+    }
+
+    #[test]
+    fn profile_stays_within_limits() {
+        let config = TrajectoryConfig::new(700.0, 800.0).max_centripetal(300.0);
+        let trajectory = Trajectory::generate(&waypoints(), config);
+        let states = trajectory.states();
+        let within = |v: f64, limit: f64| v <= limit * (1.0 + 1e-9);
+
+        assert_eq!(states.first().unwrap().velocity, 0.0);
+        assert_eq!(states.last().unwrap().velocity, 0.0);
+        assert_eq!(
+            trajectory.sample(trajectory.duration()).unwrap().velocity,
+            0.0
+        );
+        for s in states {
+            assert!(within(s.velocity, config.max_velocity), "{s:?}");
+            let centripetal = s.velocity * s.velocity * s.curvature.abs();
+            assert!(within(centripetal, config.max_centripetal), "{s:?}");
+        }
+        for w in states.windows(2) {
+            let ds = (w[1].pose.0 - w[0].pose.0).mag();
+            let acceleration = (w[1].velocity.powi(2) - w[0].velocity.powi(2)).abs() / (2.0 * ds);
+            assert!(within(acceleration, config.max_acceleration), "{w:?}");
+            assert!(w[1].t > w[0].t);
+        }
+        // the limits are reached somewhere, so the profile isn't just slow
+        assert!(states
+            .iter()
+            .any(|s| s.velocity > 0.99 * config.max_velocity));
+        assert!(states
+            .iter()
+            .any(|s| { s.velocity.powi(2) * s.curvature.abs() > 0.99 * config.max_centripetal }));
+    }
+}
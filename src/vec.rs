@@ -24,6 +24,10 @@ impl Vec2 {
     pub fn normalised(self) -> Self {
         self / self.mag()
     }
+    /// Angle from the x axis, counterclockwise, in radians.
+    pub fn angle(self) -> f64 {
+        self.y.atan2(self.x)
+    }
 }
 
 use std::ops::*;